@@ -0,0 +1,91 @@
+//! Compares the mask that power of two buffers use to find their indexes with the division every
+//! other size needs. Run with `cargo bench --bench indexing`.
+use std::{hint::black_box, time::{Duration, Instant}};
+use shm_ring::{CONTROL_SIZE, LenWidth, drain_and_fill::drain_and_fill, ringbuffer_ro::RingbufRo, ringbuffer_rw::RingbufRw};
+
+const ROUNDS: u32 = 2_000_000;
+const MSG: [u8; 24] = [7; 24];
+
+fn main() {
+    //one byte short of a power of two takes the modulo path, the power of two the mask path
+    for capacity in [4095, 4096] {
+        println!("{capacity} byte buffer");
+        report("push", bench_push(capacity));
+        report("pop", bench_pop(capacity));
+        report("drain_and_fill", bench_drain_and_fill(capacity));
+    }
+}
+
+fn report(name: &str, (elapsed, msgs): (Duration, u64)) {
+    println!("  {name:<16}{:>8.2} ns/msg", elapsed.as_nanos() as f64 / msgs as f64);
+}
+
+fn ring_buffer(capacity: usize) -> Vec<u64> {
+    vec![0; (CONTROL_SIZE + capacity).div_ceil(8)]
+}
+
+/// Times only the pushes, popping whatever is in the ring whenever it fills up
+fn bench_push(capacity: usize) -> (Duration, u64) {
+    let mut buffer = ring_buffer(capacity);
+    let ptr = buffer.as_mut_ptr() as *mut u8;
+    let mut reader = unsafe { RingbufRo::new(CONTROL_SIZE + capacity, ptr) };
+    let mut writer = unsafe { RingbufRw::new(CONTROL_SIZE + capacity, ptr) };
+    let mut dst = [0; MSG.len()];
+
+    let mut elapsed = Duration::ZERO;
+    let mut pushed = 0;
+    while pushed < ROUNDS as u64 {
+        let start = Instant::now();
+        while writer.push(black_box(&MSG)) != 0 {
+            pushed += 1;
+        }
+        elapsed += start.elapsed();
+        while reader.pop(&mut dst) != 0 {}
+    }
+    (elapsed, pushed)
+}
+
+/// Times only the pops, filling the ring up again whenever it runs dry
+fn bench_pop(capacity: usize) -> (Duration, u64) {
+    let mut buffer = ring_buffer(capacity);
+    let ptr = buffer.as_mut_ptr() as *mut u8;
+    let mut reader = unsafe { RingbufRo::new(CONTROL_SIZE + capacity, ptr) };
+    let mut writer = unsafe { RingbufRw::new(CONTROL_SIZE + capacity, ptr) };
+    let mut dst = [0; MSG.len()];
+
+    let mut elapsed = Duration::ZERO;
+    let mut popped = 0;
+    while popped < ROUNDS as u64 {
+        while writer.push(&MSG) != 0 {}
+        let start = Instant::now();
+        while reader.pop(black_box(&mut dst)) != 0 {
+            popped += 1;
+        }
+        elapsed += start.elapsed();
+    }
+    (elapsed, popped)
+}
+
+/// Times moving messages from one ring to another of the same size
+fn bench_drain_and_fill(capacity: usize) -> (Duration, u64) {
+    let size = CONTROL_SIZE + capacity;
+    let mut src = ring_buffer(capacity);
+    let mut dst = ring_buffer(capacity);
+    let (src, dst) = (src.as_mut_ptr() as *mut u8, dst.as_mut_ptr() as *mut u8);
+    let (mut src_reader, mut src_writer) = unsafe { (RingbufRo::new(size, src), RingbufRw::new(size, src)) };
+    let (mut dst_reader, mut dst_writer) = unsafe { (RingbufRo::new(size, dst), RingbufRw::new(size, dst)) };
+    let record = MSG.len() + LenWidth::default().bytes();
+    let mut msg = [0; MSG.len()];
+
+    let mut elapsed = Duration::ZERO;
+    let mut moved = 0;
+    while moved < ROUNDS as u64 {
+        while src_writer.push(&MSG) != 0 {}
+        let start = Instant::now();
+        let bytes = drain_and_fill(black_box(&mut src_reader), &mut dst_writer);
+        elapsed += start.elapsed();
+        moved += (bytes / record) as u64;
+        while dst_reader.pop(&mut msg) != 0 {}
+    }
+    (elapsed, moved)
+}
@@ -0,0 +1,265 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    io,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, atomic::{AtomicU32, Ordering, fence}},
+    task::{Context, Poll, Waker},
+    thread::JoinHandle,
+};
+use futures_core::Stream;
+use futures_sink::Sink;
+use crate::{error::RingError, futex, ringbuffer_ro::RingbufRo, ringbuffer_rw::RingbufRw};
+
+/// The consumer half of a ring as a [`Stream`] of messages. The stream never ends on its own.
+/// By default the producer wakes it through the same futex word as [`RingbufRo::pop_blocking`],
+/// so it works with a plain [`RingbufRw`] in another process. [`AsyncRingReader::with_notifier`]
+/// wakes it through an eventfd the executor polls instead.
+#[derive(Debug)]
+pub struct AsyncRingReader<'a> {
+    ring : RingbufRo<'a>,
+    wakeup : Wakeup,
+}
+
+/// The consumer's [`crate::notify::Notifier`] as seen by the executor's reactor, e.g. wrapped in a tokio
+/// `AsyncFd` or an async-io `Async`, so an [`AsyncRingReader`] can sleep without a thread of its own
+#[cfg(target_os = "linux")]
+pub trait NotifierReadiness {
+    /// Returns `Ready` once the eventfd polled readable, after draining it with [`crate::notify::Notifier::drain`].
+    /// Otherwise registers the task with the reactor, which wakes it when the eventfd becomes readable.
+    fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+//how a reader that found the ring empty gets woken up again
+enum Wakeup {
+    Watcher(Watcher),
+    #[cfg(target_os = "linux")]
+    Notifier(Box<dyn NotifierReadiness + Send>),
+}
+
+impl Debug for Wakeup {
+    fn fmt(&self, format : &mut Formatter) -> fmt::Result {
+        match self {
+            Wakeup::Watcher(watcher) => format.debug_tuple("Watcher").field(watcher).finish(),
+            #[cfg(target_os = "linux")]
+            Wakeup::Notifier(_) => format.write_str("Notifier"),
+        }
+    }
+}
+
+impl<'a> AsyncRingReader<'a> {
+    /// Spawns a dedicated OS thread that sleeps on the futex word for the stream,
+    /// and returns the error if the thread could not be spawned
+    pub fn new(ring : RingbufRo<'a>) -> io::Result<Self> {
+        let watcher = Watcher::spawn(&ring.waiters.data)?;
+        Ok(Self { ring, wakeup: Wakeup::Watcher(watcher) })
+    }
+
+    /// Wakes the stream through an eventfd registered with the executor's reactor, so no thread is spawned.
+    /// The producer has to signal the same eventfd, see [`RingbufRw::set_notifier`].
+    #[cfg(target_os = "linux")]
+    pub fn with_notifier(ring : RingbufRo<'a>, readiness : impl NotifierReadiness + Send + 'static) -> Self {
+        Self { ring, wakeup: Wakeup::Notifier(Box::new(readiness)) }
+    }
+
+    pub fn into_inner(self) -> RingbufRo<'a> {
+        self.ring
+    }
+}
+
+impl Stream for AsyncRingReader<'_> {
+    type Item = Result<Vec<u8>, RingError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let waiters = this.ring.waiters;
+        let ring = &mut this.ring;
+        match &mut this.wakeup {
+            Wakeup::Watcher(watcher) => watcher.poll(&waiters.data, cx, RingError::Empty, || next_msg(ring)).map(Some),
+            #[cfg(target_os = "linux")]
+            Wakeup::Notifier(readiness) => poll_notified(ring, readiness.as_mut(), cx).map(Some),
+        }
+    }
+}
+
+/// Tries to copy out the next message, and when the ring is empty tells the producer the consumer
+/// is idle, so its next push signals the eventfd the reactor is polling
+#[cfg(target_os = "linux")]
+fn poll_notified(ring : &mut RingbufRo<'_>, readiness : &mut dyn NotifierReadiness, cx: &mut Context<'_>) -> Poll<Result<Vec<u8>, RingError>> {
+    loop {
+        match next_msg(ring) {
+            Err(RingError::Empty) => {}
+            other => return Poll::Ready(other),
+        }
+        //a message arrived in the meantime, so no signal is coming for it
+        if !ring.announce_idle() {continue;}
+        if readiness.poll_readable(cx).is_pending() {return Poll::Pending;}
+    }
+}
+
+/// Copies the next message out of the ring
+fn next_msg(ring : &mut RingbufRo<'_>) -> Result<Vec<u8>, RingError> {
+    if !ring.is_lossy() {return ring.read().map(|msg| msg.to_vec());}
+    //a borrowed message could be overwritten while it is copied, so it is popped instead
+    let mut msg = Vec::new();
+    let msg_len = ring.pop_into_vec(&mut msg)?;
+    msg.truncate(msg_len);
+    Ok(msg)
+}
+
+/// The producer half of a ring as a [`Sink`] of messages.
+/// A message that does not fit is kept until the consumer makes room, which wakes the writer
+/// through the same futex word as [`RingbufRw::push_blocking`].
+#[derive(Debug)]
+pub struct AsyncRingWriter<'a> {
+    ring : RingbufRw<'a>,
+    watcher : Watcher,
+    pending : Option<Vec<u8>>,
+}
+
+impl<'a> AsyncRingWriter<'a> {
+    /// Spawns a dedicated OS thread that sleeps on the futex word for the sink,
+    /// and returns the error if the thread could not be spawned. Only data has an eventfd,
+    /// so unlike [`AsyncRingReader::with_notifier`] there is no way around the thread here.
+    pub fn new(ring : RingbufRw<'a>) -> io::Result<Self> {
+        let watcher = Watcher::spawn(&ring.waiters.space)?;
+        Ok(Self { ring, watcher, pending: None })
+    }
+
+    /// Gives the ring back, dropping a message that is still waiting for room
+    pub fn into_inner(self) -> RingbufRw<'a> {
+        self.ring
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), RingError>> {
+        let Some(msg) = &self.pending else { return Poll::Ready(Ok(())) };
+        let waiters = self.ring.waiters;
+        let ring = &mut self.ring;
+        let pushed = match self.watcher.poll(&waiters.space, cx, RingError::Full, || ring.try_push(msg)) {
+            Poll::Ready(pushed) => pushed,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.pending = None;
+        Poll::Ready(pushed.map(|_| ()))
+    }
+}
+
+impl<M: AsRef<[u8]>> Sink<M> for AsyncRingWriter<'_> {
+    type Error = RingError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), RingError>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: M) -> Result<(), RingError> {
+        let this = self.get_mut();
+        match this.ring.try_push(msg.as_ref()) {
+            //only copied when it has to wait
+            Err(RingError::Full) => this.pending = Some(msg.as_ref().to_vec()),
+            pushed => {pushed?;}
+        }
+        Ok(())
+    }
+
+    //pushed messages are published right away, so flushing only has to wait for the pending one
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), RingError>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), RingError>> {
+        self.get_mut().poll_pending(cx)
+    }
+}
+
+/// A helper thread that sleeps on a futex word for a task and wakes it when the peer clears the word
+#[derive(Debug)]
+struct Watcher {
+    word : *mut u32,
+    shared : Arc<(Mutex<WatchState>, Condvar)>,
+    thread : Option<JoinHandle<()>>,
+}
+
+// The thread only hands the word to the futex syscall, which checks the address itself
+unsafe impl Send for Watcher {}
+
+#[derive(Debug, Default)]
+struct WatchState {
+    waker : Option<Waker>,
+    armed : bool,
+    shutdown : bool,
+}
+
+impl Watcher {
+    fn spawn(word: &AtomicU32) -> io::Result<Self> {
+        let shared = Arc::new((Mutex::new(WatchState::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+        let address = word.as_ptr() as usize;
+        let thread = std::thread::Builder::new()
+            .name("shm_ring-watcher".into())
+            .spawn(move || watch(address as *mut u32, &thread_shared))?;
+        Ok(Self { word: word.as_ptr(), shared, thread: Some(thread) })
+    }
+
+    /// Tries `attempt`, and when it would block, announces the task is waiting and hands it to the thread
+    fn poll<T>(
+        &mut self,
+        word: &AtomicU32,
+        cx: &mut Context<'_>,
+        would_block: RingError,
+        mut attempt: impl FnMut() -> Result<T, RingError>,
+    ) -> Poll<Result<T, RingError>> {
+        match attempt() {
+            Err(e) if e == would_block => {}
+            other => return Poll::Ready(other),
+        }
+
+        let (state, cond) = &*self.shared;
+        state.lock().unwrap().waker = Some(cx.waker().clone());
+        //same handshake as futex::block_on, look once more after announcing
+        word.store(1, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        match attempt() {
+            Err(e) if e == would_block => {}
+            other => return Poll::Ready(other),
+        }
+
+        state.lock().unwrap().armed = true;
+        cond.notify_one();
+        Poll::Pending
+    }
+}
+
+fn watch(word: *mut u32, shared: &(Mutex<WatchState>, Condvar)) {
+    let (state, cond) = shared;
+    loop {
+        {
+            let mut state = cond.wait_while(state.lock().unwrap(), |state| !state.armed && !state.shutdown).unwrap();
+            if state.shutdown {return;}
+            state.armed = false;
+        }
+        //returns right away if the peer already cleared the word
+        futex::wait(word, 1, None);
+
+        let waker = {
+            let mut state = state.lock().unwrap();
+            if state.shutdown {return;}
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let (state, cond) = &*self.shared;
+        state.lock().unwrap().shutdown = true;
+        cond.notify_one();
+        //the ring is still borrowed here, so the word is alive. clearing it keeps the thread from going back to sleep
+        unsafe { (*(self.word as *const AtomicU32)).store(0, Ordering::Relaxed) };
+        futex::wake_one(self.word);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
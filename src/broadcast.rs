@@ -0,0 +1,285 @@
+use std::{fmt::{Display, Formatter}, marker::PhantomData, ptr, sync::atomic::{AtomicU64, Ordering, fence}};
+use crate::error::RingError;
+
+//every record starts with its length, records are 8 byte aligned and never wrap the end of the buffer
+const LEN_SIZE: usize = 8;
+//written in place of a length, the rest of the buffer is skipped
+const PADDING: u64 = u64::MAX;
+
+/// The tail, the reserve cursor and one head slot per reader that sit in front of the buffer
+pub const fn broadcast_control_size(readers : usize) -> usize {
+    16 + 8 * readers
+}
+
+/// What the writer does when the slowest reader has not made room yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Return [`RingError::Full`] until every reader has caught up far enough
+    BackPressure,
+    /// Never wait, readers that fall a whole buffer behind get [`RingError::Lapped`] and skip ahead
+    Overwrite,
+}
+
+/// The single writer of a broadcast ring. Every message it pushes is seen by every reader
+/// that has joined, each reader keeps its own head in a slot in front of the buffer.
+///
+/// Positions count bytes since the ring was created and never wrap, the whole buffer is usable.
+/// The writer moves the reserve cursor before it overwrites anything, so readers can tell
+/// after copying whether their message was overwritten under them.
+#[derive(Debug)]
+pub struct BroadcastWriter<'a> {
+    control : &'a Control,
+    slots : &'a [AtomicU64],
+    data : *mut u8,
+    capacity : usize,
+    overflow : Overflow,
+    _buffer : PhantomData<&'a mut [u8]>,
+}
+
+/// One reader of a broadcast ring, see [`BroadcastWriter`]. Leaves its slot when dropped.
+#[derive(Debug)]
+pub struct BroadcastReader<'a> {
+    control : &'a Control,
+    slot : &'a AtomicU64,
+    data : *const u8,
+    capacity : usize,
+    head : u64,
+    _buffer : PhantomData<&'a [u8]>,
+}
+
+// The writer is the only one writing to the buffer, readers only write their own slot
+unsafe impl Send for BroadcastWriter<'_> {}
+unsafe impl Send for BroadcastReader<'_> {}
+
+#[repr(C)]
+#[derive(Debug)]
+struct Control {
+    tail : AtomicU64,
+    reserve : AtomicU64,
+}
+
+/// # Safety
+///
+/// `data` must point to `size` bytes that are aligned to 8 and start out zeroed.
+/// Every handle for the same ring must be created with the same `size` and `readers`.
+unsafe fn layout<'a>(size : usize, data : *mut u8, readers : usize) -> (&'a Control, &'a [AtomicU64], *mut u8, usize) {
+    if data.is_null() {panic!("data cannot be null")}
+    assert_eq!(0, data as usize % LEN_SIZE, "data must be aligned to 8");
+    let control_size = broadcast_control_size(readers);
+    let capacity = size.saturating_sub(control_size) / LEN_SIZE * LEN_SIZE;
+    assert!(capacity >= 2 * LEN_SIZE, "a broadcast ring needs room for at least 16 bytes");
+    unsafe {
+        let slots = std::slice::from_raw_parts(data.add(16) as *const AtomicU64, readers);
+        (&*(data as *const Control), slots, data.add(control_size), capacity)
+    }
+}
+
+/// The number of bytes a message takes up, its length included
+fn record_len(msg_len : usize) -> usize {
+    (LEN_SIZE + msg_len).next_multiple_of(LEN_SIZE)
+}
+
+impl<'a> BroadcastWriter<'a> {
+    /// # Safety
+    ///
+    /// `data` must point to `size` bytes that are aligned to 8 and were zeroed before the first handle was made.
+    /// Every handle for the same ring must be created with the same `size` and `readers`.
+    pub unsafe fn new(size : usize, data : *mut u8, readers : usize, overflow : Overflow) -> Self {
+        let (control, slots, data, capacity) = unsafe { layout(size, data, readers) };
+        Self { control, slots, data, capacity, overflow, _buffer: PhantomData }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_tail(&self) -> u64 {
+        self.control.tail.load(Ordering::Relaxed)
+    }
+
+    /// The number of readers that have joined
+    pub fn readers(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.load(Ordering::Relaxed) != 0).count()
+    }
+
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, or 0 if it did not fit
+    pub fn push(&mut self, msg : &[u8]) -> usize {
+        self.try_push(msg).unwrap_or(0)
+    }
+
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, including its length.
+    /// Only returns [`RingError::Full`] with [`Overflow::BackPressure`].
+    pub fn try_push(&mut self, msg : &[u8]) -> Result<usize, RingError> {
+        let record = record_len(msg.len());
+        if record > self.capacity {
+            return Err(RingError::MessageTooLarge { len: msg.len(), max: self.capacity - LEN_SIZE });
+        }
+
+        let tail = self.control.tail.load(Ordering::Relaxed);
+        let offset = tail as usize % self.capacity;
+        let bytes_until_end = self.capacity - offset;
+        let padding = if record <= bytes_until_end {0} else {bytes_until_end};
+
+        if self.overflow == Overflow::BackPressure {
+            let limit = self.slowest_head().unwrap_or(tail) + self.capacity as u64;
+            if tail + (padding + record) as u64 > limit {
+                if padding != 0 && tail + padding as u64 <= limit {
+                    //publish just the padding, once the readers skip it the record can start at the beginning
+                    self.write_len(tail, offset, PADDING, padding);
+                }
+                return Err(RingError::Full);
+            }
+        }
+
+        if padding != 0 {
+            self.write_len(tail, offset, PADDING, padding);
+        }
+        let start = tail + padding as u64;
+        let end = start + record as u64;
+        self.mark_overwritten(end);
+        unsafe {
+            ptr::copy_nonoverlapping((msg.len() as u64).to_le_bytes().as_ptr(), self.data.add(start as usize % self.capacity), LEN_SIZE);
+            ptr::copy_nonoverlapping(msg.as_ptr(), self.data.add(start as usize % self.capacity + LEN_SIZE), msg.len());
+        }
+        self.control.tail.store(end, Ordering::Release);
+        Ok(record)
+    }
+
+    fn write_len(&self, tail : u64, offset : usize, len : u64, record : usize) {
+        let end = tail + record as u64;
+        self.mark_overwritten(end);
+        unsafe { ptr::copy_nonoverlapping(len.to_le_bytes().as_ptr(), self.data.add(offset), LEN_SIZE) };
+        self.control.tail.store(end, Ordering::Release);
+    }
+
+    /// Tells readers everything below `end - capacity` is about to be overwritten
+    fn mark_overwritten(&self, end : u64) {
+        self.control.reserve.store(end, Ordering::Relaxed);
+        //keeps the writes that follow from being seen before the new reserve
+        fence(Ordering::Release);
+    }
+
+    fn slowest_head(&self) -> Option<u64> {
+        //slots hold head + 1, so a zeroed slot is free
+        self.slots.iter().map(|slot| slot.load(Ordering::Acquire)).filter(|&slot| slot != 0).map(|slot| slot - 1).min()
+    }
+}
+
+impl<'a> BroadcastReader<'a> {
+    /// Takes a free slot and starts reading at the current tail, or returns `None` if every slot is taken.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`BroadcastWriter::new`]
+    pub unsafe fn join(size : usize, data : *mut u8, readers : usize) -> Option<Self> {
+        let (control, slots, data, capacity) = unsafe { layout(size, data, readers) };
+        let head = control.tail.load(Ordering::Acquire);
+        let slot = slots.iter().find(|slot| slot.compare_exchange(0, head + 1, Ordering::AcqRel, Ordering::Relaxed).is_ok())?;
+        //the writer may have moved on before it saw the slot, start from where it is now
+        let head = control.tail.load(Ordering::Acquire);
+        slot.store(head + 1, Ordering::Release);
+        Some(Self { control, slot, data, capacity, head, _buffer: PhantomData })
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_head(&self) -> u64 {
+        self.head
+    }
+
+    pub fn get_tail(&self) -> u64 {
+        self.control.tail.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.get_tail()
+    }
+
+    /// Pops the next message into `buffer` and returns its length, or 0 if there was nothing to pop.
+    /// Being lapped also returns 0, use [`BroadcastReader::try_pop`] to find out how much was skipped.
+    pub fn pop(&mut self, buffer : &mut [u8]) -> usize {
+        match self.try_pop(buffer) {
+            Ok(msg_len) => msg_len,
+            Err(e @ RingError::Corrupted { .. }) => panic!("Error: {e}"),
+            Err(_) => 0,
+        }
+    }
+
+    /// Pops the next message into `buffer` and returns its length.
+    /// When the writer overwrote messages this reader had not read, it returns [`RingError::Lapped`]
+    /// once and carries on from the newest message.
+    pub fn try_pop(&mut self, buffer : &mut [u8]) -> Result<usize, RingError> {
+        loop {
+            let tail = self.control.tail.load(Ordering::Acquire);
+            if self.head == tail {return Err(RingError::Empty);}
+            if self.was_overwritten() {return Err(self.skip_to(tail));}
+
+            let offset = self.head as usize % self.capacity;
+            let bytes_until_end = self.capacity - offset;
+            let mut len = [0u8; LEN_SIZE];
+            unsafe { ptr::copy_nonoverlapping(self.data.add(offset), len.as_mut_ptr(), LEN_SIZE) };
+            let len = u64::from_le_bytes(len);
+
+            if len == PADDING {
+                if self.was_overwritten() {return Err(self.skip_to(tail));}
+                self.advance(bytes_until_end);
+                continue;
+            }
+
+            let msg_len = len as usize;
+            let record = record_len(msg_len);
+            if len > self.capacity as u64 || record > bytes_until_end {
+                if self.was_overwritten() {return Err(self.skip_to(tail));}
+                return Err(RingError::Corrupted { head: offset, tail: tail as usize % self.capacity, len: msg_len });
+            }
+            if msg_len > buffer.len() {
+                if self.was_overwritten() {return Err(self.skip_to(tail));}
+                return Err(RingError::DestinationTooSmall { needed: msg_len, available: buffer.len() });
+            }
+
+            unsafe { ptr::copy_nonoverlapping(self.data.add(offset + LEN_SIZE), buffer.as_mut_ptr(), msg_len) };
+            //the copy may have raced the writer, it only counts if nothing was overwritten since
+            if self.was_overwritten() {return Err(self.skip_to(tail));}
+            self.advance(record);
+            return Ok(msg_len);
+        }
+    }
+
+    fn was_overwritten(&self) -> bool {
+        //pairs with the fence in mark_overwritten, orders the copies before the check
+        fence(Ordering::Acquire);
+        self.control.reserve.load(Ordering::Relaxed) > self.head + self.capacity as u64
+    }
+
+    fn skip_to(&mut self, tail : u64) -> RingError {
+        //the tail may be stale too, the newest one is always the start of a record
+        let tail = self.control.tail.load(Ordering::Acquire).max(tail);
+        let skipped = (tail - self.head) as usize;
+        self.head = tail;
+        self.slot.store(tail + 1, Ordering::Release);
+        RingError::Lapped { skipped }
+    }
+
+    fn advance(&mut self, record : usize) {
+        self.head += record as u64;
+        self.slot.store(self.head + 1, Ordering::Release);
+    }
+}
+
+impl Drop for BroadcastReader<'_> {
+    fn drop(&mut self) {
+        self.slot.store(0, Ordering::Release);
+    }
+}
+
+impl Display for BroadcastWriter<'_> {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error>{
+        write!(format, "\nBroadcast Ring Buffer: tail: {}, slowest head: {:?}, readers: {}, size: {}\n",
+                self.get_tail(),
+                self.slowest_head(),
+                self.readers(),
+                self.capacity)
+    }
+}
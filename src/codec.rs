@@ -0,0 +1,86 @@
+use std::{marker::PhantomData, mem::size_of, ptr, slice};
+use crate::error::SizeMismatch;
+
+/// Turns values into message bytes and back, for [`crate::typed::TypedRingWriter`] and [`crate::typed::TypedRingReader`]
+pub trait Codec<T> {
+    type Error;
+
+    /// Returns the bytes for `value`. Codecs that have to serialize write them into `scratch`,
+    /// which is reused between messages, codecs that can view the value in place just return it.
+    fn encode<'b>(value : &'b T, scratch : &'b mut Vec<u8>) -> Result<&'b [u8], Self::Error>;
+
+    fn decode(bytes : &[u8]) -> Result<T, Self::Error>;
+}
+
+/// # Safety
+///
+/// Implementors must be plain old data: `#[repr(C)]` or a primitive, no padding bytes,
+/// no pointers or references, and every bit pattern must be a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => { $(unsafe impl Pod for $ty {})* };
+}
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Sends [`Pod`] values as their raw bytes. Encoding never copies, decoding copies the bytes once.
+/// Both sides must agree on the layout, which is only the case on the same target.
+#[derive(Debug)]
+pub struct PodCodec<T>(PhantomData<T>);
+
+impl<T: Pod> Codec<T> for PodCodec<T> {
+    type Error = SizeMismatch;
+
+    fn encode<'b>(value : &'b T, _scratch : &'b mut Vec<u8>) -> Result<&'b [u8], SizeMismatch> {
+        Ok(unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) })
+    }
+
+    fn decode(bytes : &[u8]) -> Result<T, SizeMismatch> {
+        if bytes.len() != size_of::<T>() {
+            return Err(SizeMismatch { expected: size_of::<T>(), found: bytes.len() });
+        }
+        //the payload is only aligned in a contiguous ring
+        Ok(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+    }
+}
+
+/// Sends any serde type with bincode
+#[cfg(feature = "bincode")]
+#[derive(Debug)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for BincodeCodec {
+    type Error = bincode::Error;
+
+    fn encode<'b>(value : &'b T, scratch : &'b mut Vec<u8>) -> Result<&'b [u8], bincode::Error> {
+        scratch.clear();
+        bincode::serialize_into(&mut *scratch, value)?;
+        Ok(scratch)
+    }
+
+    fn decode(bytes : &[u8]) -> Result<T, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// Sends any serde type with postcard, which is more compact than bincode for small integers
+#[cfg(feature = "postcard")]
+#[derive(Debug)]
+pub struct PostcardCodec;
+
+#[cfg(feature = "postcard")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for PostcardCodec {
+    type Error = postcard::Error;
+
+    fn encode<'b>(value : &'b T, scratch : &'b mut Vec<u8>) -> Result<&'b [u8], postcard::Error> {
+        scratch.clear();
+        *scratch = postcard::to_extend(value, std::mem::take(scratch))?;
+        Ok(scratch)
+    }
+
+    fn decode(bytes : &[u8]) -> Result<T, postcard::Error> {
+        postcard::from_bytes(bytes)
+    }
+}
@@ -1,105 +1,177 @@
 use crate::ringbuffer_ro::RingbufRo;
 use crate::ringbuffer_rw::RingbufRw;
-use crate::SZ_OF_USIZE;
+use crate::{LenWidth, copy_bytes};
+use crate::error::RingError;
+use std::sync::atomic::Ordering;
 
 
 /// This function moves messages from the reader to the writer and returns the number of bytes written
 pub fn drain_and_fill(reader: &mut RingbufRo, writer: &mut RingbufRw) -> usize {
+    //a lossy producer can overwrite what is being copied, so every message is checked on the way out
+    if reader.lossy {return drain_lossy(reader, writer);}
+    //contiguous rings pad and align their messages and rings with different length fields
+    //frame them differently, so they cant be copied as one block
+    if reader.contiguous || writer.contiguous || reader.len_width != writer.len_width {return drain_msg_by_msg(reader, writer);}
+
     //---------------------How much room is there in the writer buffer-----------------------
-    let whead = *writer.head;
-    let wtail = *writer.tail;
+    let whead = writer.refresh_head();
+    let wtail = writer.tail.load(Ordering::Relaxed);
 
     //calculate how much space is available in the writers ringbuffer
-    let free_space = writer.buffer.len() - calc_curr_bytes(whead, wtail, writer.buffer.len()) - 1;
+    let free_space = writer.get_size().saturating_sub(calc_curr_bytes(whead, wtail));
     
     if free_space == 0 {return 0;} //Do i need to do this if it will just result in an empty memcopy?
 
-    let rhead = *reader.head;//this guy is a phantom head that we will use to count messages
-    let rtail = *reader.tail;//this guy can be a race condition
+    let rhead = reader.head.load(Ordering::Relaxed);//this guy is a phantom head that we will use to count messages
+    let rtail = reader.refresh_tail();//every byte up to rtail is visible
 
-    let accumulator = bytes_within_limit(free_space, rhead, rtail, reader.buffer);
+    let accumulator = bytes_within_limit(free_space, rhead, rtail, reader.len_width, reader.ring());
 
     if accumulator == 0 { return 0;}//Do i need to do this if it will just result in an empty memcopy?
 
-    let (first_part, second_part) = get_parts(accumulator, rhead, reader.buffer);
-
-    copy_in_parts(first_part, second_part, wtail, writer.buffer);
+    //each side is in one part if its buffer is mirrored
+    let (first_part, second_part) = reader.parts(accumulator, reader.offset(rhead));
+    let woffset = writer.offset(wtail);
+    copy_between_parts(first_part, second_part.unwrap_or_default(), writer.parts_mut(accumulator, woffset));
 
 //---------------------update tail and head
-    *writer.tail = (wtail + accumulator) % writer.buffer.len();
-    *reader.head = (rhead + accumulator) % reader.buffer.len();
+    writer.publish_tail(wtail + accumulator as u64);
+    reader.advance_head(rhead, rhead + accumulator as u64);
         
     accumulator
     
 }
 
-fn calc_curr_bytes(head: usize, tail: usize, size: usize) -> usize{
-        if tail > head {
-            tail - head
-        } else if tail < head {
-            size + tail - head
-        } else {
-            0
+/// Moves whole messages one at a time, reframing each one for the writer
+fn drain_msg_by_msg(reader: &mut RingbufRo, writer: &mut RingbufRw) -> usize {
+    let mut written = 0;
+    while let Ok(msg_len) = reader.peek_len() {
+        let Ok(mut grant) = writer.reserve(msg_len) else { break };
+        let Ok(msg) = reader.read() else { break };
+
+        let (first_part, second_part) = msg.parts();
+        copy_between_parts(first_part, second_part.unwrap_or_default(), grant.parts_mut());
+        //the grant is abandoned if the message turns out to have been dropped under us
+        if msg.commit().is_err() {continue;}
+        written += grant.record_len();
+        grant.commit();
+    }
+    written
+}
+
+/// Moves messages out of a lossy reader through a scratch buffer, since a borrowed message
+/// could be overwritten before it is copied into the writer
+fn drain_lossy(reader: &mut RingbufRo, writer: &mut RingbufRw) -> usize {
+    let mut written = 0;
+    let mut msg = Vec::new();
+    while let Ok(msg_len) = reader.peek_len() {
+        //make sure there is room before the message is taken out of the reader
+        if writer.reserve(msg_len).is_err() {break;}
+        msg.resize(msg_len, 0);
+        //the message can be dropped and replaced by a bigger one in between, then just look again
+        let Ok(msg_len) = reader.try_pop(&mut msg) else { continue };
+        let Ok(record) = writer.try_push(&msg[..msg_len]) else { break };
+        written += record;
+    }
+    written
+}
+
+/// Copies a message that is in up to two parts into a region that is in up to two parts
+fn copy_between_parts(first_part: &[u8], second_part: &[u8], (dst_first, dst_second): PartsMut) {
+    let dst_parts = [dst_first, dst_second.unwrap_or_default()];
+    let mut dst_part = 0;
+    let mut dst_offset = 0;
+    for mut src in [first_part, second_part] {
+        while !src.is_empty() {
+            let dst = &mut dst_parts[dst_part][dst_offset..];
+            let len = dst.len().min(src.len());
+            copy_bytes(&mut dst[..len], &src[..len]);
+            src = &src[len..];
+            dst_offset += len;
+            if dst_offset == dst_parts[dst_part].len() {
+                dst_part += 1;
+                dst_offset = 0;
+            }
         }
+    }
 }
 
-///This function returns the length of the next messages payload, excluding the length field [length|payload]
-fn peek(head: usize, tail: usize, buffer: &[u8]) -> usize {
-    //if buffer is empty or there arent enough bytes to fill the msg_len
-    let curr_bytes = calc_curr_bytes(head, tail, buffer.len());
+/// The bytes between two positions, a head past the tail counts as empty
+pub(crate) fn calc_curr_bytes(head: u64, tail: u64) -> usize{
+    tail.saturating_sub(head) as usize
+}
 
-    if curr_bytes < SZ_OF_USIZE {return 0;}//I think this also covers the case when its empty
-    // if is_empty(head, tail) || curr_bytes < SZ_OF_USIZE {return 0;}
+/// Where the position `pos` falls in a buffer of `size` bytes. A power of two size only needs a mask,
+/// any other size costs a division. The size of a ring never changes, so the check always goes the same way.
+#[inline(always)]
+pub(crate) fn offset(pos: u64, size: usize) -> usize {
+    if size.is_power_of_two() {
+        //the mask keeps only low bits, so truncating the position first does not change them
+        pos as usize & (size - 1)
+    } else {
+        (pos % size as u64) as usize
+    }
+}
+
+///This function returns the length of the next messages payload, excluding the length field [length|payload].
+///A zero length message is Ok(0), an empty ring is Err(RingError::Empty)
+pub(crate) fn peek(head_pos: u64, tail_pos: u64, len_width: LenWidth, buffer: &[u8]) -> Result<usize, RingError> {
+    let (head, tail) = (offset(head_pos, buffer.len()), offset(tail_pos, buffer.len()));
+    let curr_bytes = calc_curr_bytes(head_pos, tail_pos);
+    let width = len_width.bytes();
+
+    //the head can never pass the tail or fall more than a buffer behind it
+    if head_pos > tail_pos || curr_bytes > buffer.len() {return Err(RingError::Corrupted { head, tail, len: 0 });}
+    if curr_bytes == 0 {return Err(RingError::Empty);}
+    //the producer only ever publishes whole messages, so there should always be a whole msg_len
+    if curr_bytes < width {return Err(RingError::Corrupted { head, tail, len: 0 });}
 
+    let msg_len = if head + width > buffer.len() {
 //the msg_len field is wrapping
-    if head + SZ_OF_USIZE > buffer.len() { 
-//EXTRA SAD CASE
         let bytes_until_end = buffer.len() - head;
         let first_half = &buffer[head..];
-        let second_half = &buffer[..SZ_OF_USIZE-bytes_until_end];
-        //Combine the 2 parts into a usize to find the length of the message
-        let mut msg_len_bytes: [u8;SZ_OF_USIZE] = [0;SZ_OF_USIZE];
+        let second_half = &buffer[..width-bytes_until_end];
+        //Combine the 2 parts to find the length of the message
+        let mut msg_len_bytes = [0u8;8];
         msg_len_bytes[..first_half.len()].copy_from_slice(first_half);
-        msg_len_bytes[first_half.len()..].copy_from_slice(second_half);
-        let msg_len = usize::from_le_bytes(msg_len_bytes);
-
-        if msg_len <= curr_bytes - SZ_OF_USIZE { //we've already wrapped so we dont have to worry about the msg wrapping
-            msg_len
-        } else {
-            panic!("Error: the msg_len is greater than the number of bytes available");
-        } 
+        msg_len_bytes[first_half.len()..width].copy_from_slice(second_half);
+        len_width.decode(&msg_len_bytes)
     } else {
         //there are at least enough bytes to get the msg_len field
-        let msg_len_slice = &buffer[head..head+SZ_OF_USIZE];
-        let msg_len= usize::from_le_bytes(msg_len_slice.try_into().unwrap());
+        len_width.decode(&buffer[head..head+width])
+    };
 
-        if msg_len <= curr_bytes - SZ_OF_USIZE {
-            msg_len
-        }else { //there were not enough bytes to fulfil the msg_len, this should never happen
-            panic!("Error: not enough bytes to fill msg_len");
-        }
+    if msg_len > curr_bytes - width { //there were not enough bytes to fulfil the msg_len, this should never happen
+        return Err(RingError::Corrupted { head, tail, len: msg_len });
     }
+    Ok(msg_len)
 }
 
 /// This function calculates the largest number of bytes within the ringbuffer for a given head and tail that fits within a limit, 
 /// quantized by whole message boundaries
-fn bytes_within_limit(limit: usize, mut phantom_head: usize, phantom_tail: usize, buffer: &[u8]) -> usize {
+fn bytes_within_limit(limit: usize, mut phantom_head: u64, phantom_tail: u64, len_width: LenWidth, buffer: &[u8]) -> usize {
 //---------------------Whats the largest "contiguous" array of whole messages that will fit in the writer
     let mut accumulator: usize = 0;//what if there arent enough messages?
     while accumulator <= limit {
-        let next_msg_len = peek(phantom_head, phantom_tail, buffer) + SZ_OF_USIZE;
-        if next_msg_len - SZ_OF_USIZE == 0 || accumulator + next_msg_len > limit {//No more messages or too many to fit
+        let next_msg_len = match peek(phantom_head, phantom_tail, len_width, buffer) {
+            Ok(msg_len) => msg_len + len_width.bytes(),//zero length messages still count
+            Err(_) => break,//No more messages, a corrupt one is left for the reader to report
+        };
+        if accumulator + next_msg_len > limit {//too many to fit
             break;
         }
         accumulator += next_msg_len;
-        phantom_head = (phantom_head + next_msg_len) % buffer.len();
+        phantom_head += next_msg_len as u64;
     }
 
     accumulator
 }
 
+/// A region of the buffer, the second part is only there if the region wraps the end of the buffer
+pub(crate) type Parts<'a> = (&'a [u8], Option<&'a [u8]>);
+
 /// given a pre calculated contiguous region of messages (accumulator), get references to them
-fn get_parts(accumulator: usize, phantom_head: usize, buffer: &[u8]) -> (&[u8], Option<&[u8]>){
+pub(crate) fn get_parts(accumulator: usize, phantom_head: usize, buffer: &[u8]) -> Parts<'_> {
     //---------------------Get the contiguous messages in potentially 2 parts
     let first_part: &[u8];
     let mut second_part: Option<&[u8]> = None;
@@ -114,7 +186,21 @@ fn get_parts(accumulator: usize, phantom_head: usize, buffer: &[u8]) -> (&[u8],
     (first_part, second_part)
 }
 
-fn copy_in_parts(first_part: &[u8], second_part: Option<&[u8]>, phantom_tail: usize, buffer: &mut [u8]){
+/// A writable region of the buffer, the second part is only there if the region wraps the end of the buffer
+pub(crate) type PartsMut<'a> = (&'a mut [u8], Option<&'a mut [u8]>);
+
+/// given a region that is about to be written at phantom_tail, get mutable references to it
+pub(crate) fn get_parts_mut(accumulator: usize, phantom_tail: usize, buffer: &mut [u8]) -> PartsMut<'_> {
+    if phantom_tail + accumulator > buffer.len() {//split the region into two parts
+        let bytes_until_end = buffer.len() - phantom_tail;
+        let (second_part, first_part) = buffer.split_at_mut(phantom_tail);
+        (first_part, Some(&mut second_part[..accumulator-bytes_until_end]))
+    } else {
+        (&mut buffer[phantom_tail..phantom_tail+accumulator], None)
+    }
+}
+
+pub(crate) fn copy_in_parts(first_part: &[u8], second_part: Option<&[u8]>, phantom_tail: usize, buffer: &mut [u8]){
     let bytes_until_end = buffer.len() - phantom_tail;
 
     let first_part_len = first_part.len();
@@ -0,0 +1,128 @@
+use std::{fmt::{Display, Formatter}, io};
+
+/// Errors returned when creating or attaching to a shared memory segment
+#[derive(Debug)]
+pub enum ShmError {
+    /// A system call failed
+    Io(io::Error),
+    /// The segment does not start with [`crate::header::MAGIC`], so it is not a ring
+    BadMagic { found: u64 },
+    /// The segment was laid out by a different version of this crate
+    VersionMismatch { expected: u32, found: u32 },
+    /// The segment frames messages with a length field width that is not a [`crate::LenWidth`]
+    LenWidthMismatch { expected: u32, found: u32 },
+    /// The capacity in the header does not match the size of the segment
+    CapacityMismatch { header: u64, segment: u64 },
+    /// The header requests features this build does not understand
+    UnsupportedFlags { flags: u64 },
+    /// The segment holds slots of a different size than the type it is attached as, no slots at all,
+    /// or slots where a ring of length prefixed messages was expected, which is reported as `expected: 0`
+    SlotSizeMismatch { expected: usize, found: usize },
+}
+
+impl Display for ShmError {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            ShmError::Io(e) => write!(format, "shared memory error: {e}"),
+            ShmError::BadMagic { found } => write!(format, "not a ring segment: bad magic {found:#x}"),
+            ShmError::VersionMismatch { expected, found } => write!(format, "ring layout version {found}, expected {expected}"),
+            ShmError::LenWidthMismatch { expected, found } => write!(format, "ring length field is {found} bytes, expected {expected}"),
+            ShmError::CapacityMismatch { header, segment } => write!(format, "ring capacity {header} does not fit the segment's {segment} bytes"),
+            ShmError::UnsupportedFlags { flags } => write!(format, "unsupported ring flags {flags:#x}"),
+            ShmError::SlotSizeMismatch { expected, found } => write!(format, "ring slots are {found} bytes, expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for ShmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShmError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ShmError {
+    fn from(e: io::Error) -> Self {
+        ShmError::Io(e)
+    }
+}
+
+/// Errors returned by the fallible push and pop operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingError {
+    /// There is not enough free space for the message right now
+    Full,
+    /// There are no messages to read
+    Empty,
+    /// The message can never fit, even in an empty ring
+    MessageTooLarge { len: usize, max: usize },
+    /// The destination cannot hold the next message, which is left in the ring
+    DestinationTooSmall { needed: usize, available: usize },
+    /// The length field at `head` does not describe a message that fits between `head` and `tail`
+    Corrupted { head: usize, tail: usize, len: usize },
+    /// The producer overwrote messages this reader had not read yet, it skipped ahead by `skipped` bytes
+    Lapped { skipped: usize },
+}
+
+impl Display for RingError {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            RingError::Full => write!(format, "ring is full"),
+            RingError::Empty => write!(format, "ring is empty"),
+            RingError::MessageTooLarge { len, max } => write!(format, "message of {len} bytes exceeds the largest possible message of {max} bytes"),
+            RingError::DestinationTooSmall { needed, available } => write!(format, "message of {needed} bytes does not fit in a buffer of {available} bytes"),
+            RingError::Corrupted { head, tail, len } => write!(format, "corrupt message length {len} at head {head}, tail {tail}"),
+            RingError::Lapped { skipped } => write!(format, "reader was lapped and skipped {skipped} bytes"),
+        }
+    }
+}
+
+impl std::error::Error for RingError {}
+
+/// Errors returned by the typed channels in [`crate::typed`], from the ring or from the codec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedError<E> {
+    Ring(RingError),
+    Codec(E),
+}
+
+impl<E: Display> Display for TypedError<E> {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            TypedError::Ring(e) => write!(format, "{e}"),
+            TypedError::Codec(e) => write!(format, "codec error: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for TypedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TypedError::Ring(e) => Some(e),
+            TypedError::Codec(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<RingError> for TypedError<E> {
+    fn from(e: RingError) -> Self {
+        TypedError::Ring(e)
+    }
+}
+
+/// A message decoded with [`crate::codec::PodCodec`] is not the size of the type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl Display for SizeMismatch {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(format, "message of {} bytes, expected {}", self.found, self.expected)
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
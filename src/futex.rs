@@ -0,0 +1,96 @@
+use std::{sync::atomic::{AtomicU32, Ordering, fence}, time::{Duration, Instant}};
+use crate::error::RingError;
+
+/// The words each side parks on while it waits for the other. They live in the control block
+/// right after the head, so they are shared between processes like the rest of the ring.
+/// A word is 1 while its side is (about to be) asleep and is cleared by the peer that wakes it.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Waiters {
+    /// The consumer is waiting for a message
+    pub(crate) data : AtomicU32,
+    /// The producer is waiting for free space
+    pub(crate) space : AtomicU32,
+}
+
+/// Keeps calling `attempt` until it returns something other than `would_block`, parking on `word`
+/// in between. Gives up with `would_block` once `timeout` has passed.
+pub(crate) fn block_on<T>(
+    word: &AtomicU32,
+    timeout: Option<Duration>,
+    would_block: RingError,
+    mut attempt: impl FnMut() -> Result<T, RingError>,
+) -> Result<T, RingError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        match attempt() {
+            Err(e) if e == would_block => {}
+            other => return other,
+        }
+
+        //announce we are going to sleep, then look once more so progress made in between is not missed
+        word.store(1, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        match attempt() {
+            Err(e) if e == would_block => {}
+            other => return other,
+        }
+
+        let remaining = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return Err(would_block),
+            },
+            None => None,
+        };
+        wait(word.as_ptr(), 1, remaining);
+    }
+}
+
+/// Called after publishing progress. Only makes a system call when the peer announced it is asleep,
+/// and returns whether it did.
+pub(crate) fn wake(word: &AtomicU32) -> bool {
+    //pairs with the fence in block_on, either we see the waiter or it sees our progress
+    fence(Ordering::SeqCst);
+    if word.load(Ordering::Relaxed) != 0 && word.swap(0, Ordering::Relaxed) != 0 {
+        wake_one(word.as_ptr());
+        return true;
+    }
+    false
+}
+
+/// Marks the side that owns `word` as idle, then checks with `is_idle` that there is still nothing to do.
+/// The same handshake as [`block_on`], for callers that sleep somewhere else, like in epoll.
+pub(crate) fn announce(word: &AtomicU32, is_idle: impl FnOnce() -> bool) -> bool {
+    word.store(1, Ordering::Relaxed);
+    fence(Ordering::SeqCst);
+    is_idle()
+}
+
+/// Sleeps until `word` is woken, unless it no longer holds `expected`.
+/// Takes a pointer so a helper thread can wait on a word it does not borrow, the kernel checks the address.
+#[cfg(target_os = "linux")]
+pub(crate) fn wait(word: *mut u32, expected: u32, timeout: Option<Duration>) {
+    let timespec = timeout.map(|timeout| libc::timespec {
+        tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    });
+    let timespec_ptr = timespec.as_ref().map_or(std::ptr::null(), |timespec| timespec as *const libc::timespec);
+    //not FUTEX_PRIVATE_FLAG, the peer is usually another process
+    unsafe { libc::syscall(libc::SYS_futex, word, libc::FUTEX_WAIT, expected, timespec_ptr) };
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn wake_one(word: *mut u32) {
+    unsafe { libc::syscall(libc::SYS_futex, word, libc::FUTEX_WAKE, 1) };
+}
+
+//without futexes we can only nap and look again
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn wait(_word: *mut u32, _expected: u32, timeout: Option<Duration>) {
+    let nap = Duration::from_micros(50);
+    std::thread::sleep(timeout.map_or(nap, |timeout| timeout.min(nap)));
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn wake_one(_word: *mut u32) {}
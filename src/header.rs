@@ -0,0 +1,133 @@
+use std::{mem::size_of, sync::atomic::{AtomicU64, Ordering}};
+use crate::{CACHE_LINE, CONTROL_SIZE, LenWidth, error::ShmError};
+
+/// "SHMRING\0" read as a little endian u64
+pub const MAGIC: u64 = u64::from_le_bytes(*b"SHMRING\0");
+/// Bumped whenever the layout of a segment changes
+pub const VERSION: u32 = 6;
+/// Messages never wrap the end of the buffer, see [`crate::ringbuffer_rw::RingbufRw::set_contiguous`]
+pub const FLAG_CONTIGUOUS: u64 = 1 << 0;
+/// The producer drops old messages to make room, see [`crate::ringbuffer_rw::RingbufRw::set_lossy`]
+pub const FLAG_LOSSY: u64 = 1 << 1;
+/// The buffer starts on a page of its own and is mapped twice, see [`crate::ringbuffer_rw::RingbufRw::new_mirrored`]
+pub const FLAG_MIRRORED: u64 = 1 << 2;
+/// The segment asks for huge pages where the system has them, see [`crate::shm::ShmRing::page_size`]
+pub const FLAG_HUGE_PAGES: u64 = 1 << 3;
+/// The feature flags this build understands
+pub const KNOWN_FLAGS: u64 = FLAG_CONTIGUOUS | FLAG_LOSSY | FLAG_MIRRORED | FLAG_HUGE_PAGES;
+/// Number of bytes the header occupies at the start of a segment
+pub const HEADER_SIZE: usize = size_of::<SegmentHeader>();
+
+/// The fixed header at the start of every segment, one [`CACHE_LINE`] long. It is followed by the tail, the head,
+/// the [`crate::futex::Waiters`] and then the buffer, each on its own line, exactly as [`crate::ringbuffer_ro::RingbufRo::new`] expects them.
+/// The control block normally comes right after the header, but it is moved up to end where the buffer starts,
+/// which in a mirrored segment is the first page boundary after the header.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SegmentHeader {
+    magic : AtomicU64,
+    version : u32,
+    len_width : u32,
+    capacity : u64,
+    flags : u64,
+    slot_size : u64,
+    buffer_offset : u64,
+    //pads the header to a whole cache line, so the control block after it starts on one
+    _padding : [u8; CACHE_LINE - 48],
+}
+
+impl SegmentHeader {
+    /// # Safety
+    ///
+    /// Writes a fresh header to `data`, which must point to at least [`HEADER_SIZE`] writable bytes
+    /// aligned to 8. The magic is published last, so a peer never sees a half written header.
+    pub unsafe fn init<'a>(data : *mut u8, capacity : usize, flags : u64) -> &'a SegmentHeader {
+        unsafe { Self::init_with(data, capacity, flags, LenWidth::default(), 0, HEADER_SIZE + CONTROL_SIZE) }
+    }
+
+    /// # Safety
+    ///
+    /// Like [`SegmentHeader::init`], with the width of the length fields and, for a
+    /// [`crate::slot_ring::SlotRing`], the size of its slots. `capacity` is still in bytes
+    /// and must be a multiple of `slot_size`, which is 0 for a ring of length prefixed messages.
+    /// The buffer starts `buffer_offset` bytes into the segment, right after the control block.
+    pub unsafe fn init_with<'a>(data : *mut u8, capacity : usize, flags : u64, len_width : LenWidth, slot_size : usize, buffer_offset : usize) -> &'a SegmentHeader {
+        let header = data as *mut SegmentHeader;
+        unsafe {
+            (*header).version = VERSION;
+            (*header).len_width = len_width.bytes() as u32;
+            (*header).capacity = capacity as u64;
+            (*header).flags = flags;
+            (*header).slot_size = slot_size as u64;
+            (*header).buffer_offset = buffer_offset as u64;
+            (*header).magic.store(MAGIC, Ordering::Release);
+            &*header
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `data` must point to a mapping of `size` bytes aligned to 8. The header is only
+    /// returned if it was written by a compatible build and describes a ring that fits in `size`.
+    pub unsafe fn attach<'a>(data : *const u8, size : usize) -> Result<&'a SegmentHeader, ShmError> {
+        if size < HEADER_SIZE {
+            return Err(ShmError::CapacityMismatch { header: 0, segment: size as u64 });
+        }
+        let header = unsafe { &*(data as *const SegmentHeader) };
+        header.validate(size)?;
+        Ok(header)
+    }
+
+    /// Checks the header against this build and against the size of the mapping it lives in
+    pub fn validate(&self, size : usize) -> Result<(), ShmError> {
+        let magic = self.magic.load(Ordering::Acquire);
+        if magic != MAGIC {
+            return Err(ShmError::BadMagic { found: magic });
+        }
+        if self.version != VERSION {
+            return Err(ShmError::VersionMismatch { expected: VERSION, found: self.version });
+        }
+        if LenWidth::from_bytes(self.len_width).is_none() {
+            return Err(ShmError::LenWidthMismatch { expected: LenWidth::default().bytes() as u32, found: self.len_width });
+        }
+        if self.flags & !KNOWN_FLAGS != 0 {
+            return Err(ShmError::UnsupportedFlags { flags: self.flags & !KNOWN_FLAGS });
+        }
+        if self.capacity < 2 || self.buffer_offset < (HEADER_SIZE + CONTROL_SIZE) as u64 || !self.buffer_offset.is_multiple_of(CACHE_LINE as u64)
+            || self.buffer_offset.saturating_add(self.capacity) > size as u64 {
+            return Err(ShmError::CapacityMismatch { header: self.capacity, segment: size as u64 });
+        }
+        if self.slot_size != 0 && (!self.capacity.is_multiple_of(self.slot_size) || self.capacity / self.slot_size < 2) {
+            return Err(ShmError::CapacityMismatch { header: self.capacity, segment: size as u64 });
+        }
+        Ok(())
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The width of the length field in bytes, see [`LenWidth::from_bytes`]
+    pub fn len_width(&self) -> u32 {
+        self.len_width
+    }
+
+    /// The size of the buffer in bytes, not counting the header or the control block in front of it
+    pub fn capacity(&self) -> usize {
+        self.capacity as usize
+    }
+
+    pub fn flags(&self) -> u64 {
+        self.flags
+    }
+
+    /// The size of each slot of a [`crate::slot_ring::SlotRing`], or 0 for a ring of length prefixed messages
+    pub fn slot_size(&self) -> usize {
+        self.slot_size as usize
+    }
+
+    /// Where the buffer starts in the segment, the control block is the [`CONTROL_SIZE`] bytes in front of it
+    pub fn buffer_offset(&self) -> usize {
+        self.buffer_offset as usize
+    }
+}
@@ -4,5 +4,122 @@ pub mod ringbuffer_rw;
 pub mod drain_and_fill;
 #[cfg(feature = "avx2")]
 pub mod avx;
+/// This module defines the versioned header written at the start of every segment
+pub mod header;
+/// This module defines the errors returned by this crate
+pub mod error;
+/// This module creates and maps the shared memory segments that hold a ring
+#[cfg(unix)]
+pub mod shm;
+/// This module lets either side of a ring sleep until the other makes progress
+pub mod futex;
+/// This module defines a ring that several producers can push to at once
+pub mod mpsc;
+/// This module defines a ring that one producer broadcasts to several readers
+pub mod broadcast;
+/// This module defines a ring of fixed size values stored without a length field
+pub mod slot_ring;
+/// This module defines how typed values are turned into messages
+pub mod codec;
+/// This module sends and receives typed values over the two halves of a ring
+pub mod typed;
+/// This module defines an eventfd the producer signals, for consumers that sleep in epoll
+#[cfg(target_os = "linux")]
+pub mod notify;
+/// This module wraps the two halves of a ring in a Stream and a Sink
+#[cfg(feature = "async")]
+pub mod async_ring;
 
-pub const SZ_OF_USIZE: usize = core::mem::size_of::<usize>();
\ No newline at end of file
+/// The size of a `usize` on this target, which is no longer the width of the length field
+#[deprecated(note = "length fields have a fixed width now, use `LenWidth::bytes`")]
+pub const SZ_OF_USIZE: usize = core::mem::size_of::<usize>();
+/// The tail, the head and the [`futex::Waiters`] each get a line of this many bytes, so the producer
+/// writing its tail does not invalidate the line the consumer writes its head to, and the other way round.
+/// 128 rather than 64 since x86 prefetches lines in pairs and Apple silicon has 128 byte lines.
+pub const CACHE_LINE: usize = 128;
+/// The tail, the head and the [`futex::Waiters`] that sit in front of the buffer, one [`CACHE_LINE`] each.
+/// The buffer that follows starts on a line of its own too.
+pub const CONTROL_SIZE: usize = CACHE_LINE * 3;
+
+/// Written in place of a length field to tell the reader the rest of the buffer is padding
+/// and the next message starts at the beginning, see [`ringbuffer_rw::RingbufRw::set_contiguous`].
+/// On the wire it is the all ones value of the ring's [`LenWidth`].
+pub const PAD_MARKER: usize = usize::MAX;
+
+/// The width of the length field in front of every message. Unlike a `usize` it is the same on
+/// every target, so 32 and 64 bit processes can share a ring. It is recorded in the segment header
+/// and both halves of a ring must agree on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LenWidth {
+    U16,
+    U32,
+    #[default]
+    U64,
+}
+
+impl LenWidth {
+    /// The number of bytes the length field takes up
+    pub const fn bytes(self) -> usize {
+        match self {
+            LenWidth::U16 => 2,
+            LenWidth::U32 => 4,
+            LenWidth::U64 => 8,
+        }
+    }
+
+    /// The width that takes up `bytes` bytes, as recorded in [`header::SegmentHeader::len_width`]
+    pub const fn from_bytes(bytes: u32) -> Option<Self> {
+        match bytes {
+            2 => Some(LenWidth::U16),
+            4 => Some(LenWidth::U32),
+            8 => Some(LenWidth::U64),
+            _ => None,
+        }
+    }
+
+    /// The longest payload the field can describe, its all ones value is taken by the [`PAD_MARKER`]
+    pub const fn max_len(self) -> usize {
+        let max = self.all_ones() - 1;
+        if max > (usize::MAX - 1) as u64 {usize::MAX - 1} else {max as usize}
+    }
+
+    const fn all_ones(self) -> u64 {
+        u64::MAX >> (64 - 8 * self.bytes())
+    }
+
+    /// The field for `len` in little endian, only the first [`LenWidth::bytes`] of it belong to the field
+    pub(crate) fn encode(self, len: usize) -> [u8; 8] {
+        let len = if len == PAD_MARKER {u64::MAX} else {len as u64};
+        len.to_le_bytes()
+    }
+
+    /// Reads the field at the start of `field`, which must hold at least [`LenWidth::bytes`]
+    pub(crate) fn decode(self, field: &[u8]) -> usize {
+        let mut bytes = [0u8; 8];
+        bytes[..self.bytes()].copy_from_slice(&field[..self.bytes()]);
+        let len = u64::from_le_bytes(bytes);
+        if len == self.all_ones() {return PAD_MARKER;}
+        //a length this target cannot address can never fit in the ring, so it is reported as corrupt
+        usize::try_from(len).unwrap_or(usize::MAX - 1)
+    }
+}
+
+/// The number of bytes a message takes up in the ring. Contiguous rings keep every
+/// length field aligned to its width, so payloads can be cast in place.
+pub(crate) fn record_len(msg_len: usize, len_width: LenWidth, contiguous: bool) -> usize {
+    let width = len_width.bytes();
+    if contiguous {
+        (width + msg_len).next_multiple_of(width)
+    } else {
+        width + msg_len
+    }
+}
+
+/// Copies a payload into or out of the ring, using the avx2 path when it is enabled
+#[inline(always)]
+pub(crate) fn copy_bytes(dst: &mut [u8], src: &[u8]) {
+    #[cfg(feature = "avx2")]
+    unsafe { avx::SliceExt::copy_from_slice_avx(dst, src) }
+    #[cfg(not(feature = "avx2"))]
+    dst.copy_from_slice(src)
+}
@@ -0,0 +1,218 @@
+use std::{fmt::{Display, Formatter}, marker::PhantomData, ptr, sync::atomic::{AtomicU64, Ordering}};
+use crate::error::RingError;
+
+/// The reserve cursor and the head that sit in front of the buffer of an mpsc ring
+pub const MPSC_CONTROL_SIZE: usize = 16;
+
+//every record starts with one of these, it is 0 until the record is committed
+const HEADER_SIZE: usize = 8;
+const COMMITTED: u64 = 1 << 63;
+//the rest of the buffer is skipped, the next record starts at the beginning
+const PADDING: u64 = 1 << 62;
+const LEN_MASK: u64 = PADDING - 1;
+
+/// A ring that any number of producers can push to at the same time, drained by a single [`MpscConsumer`].
+///
+/// Producers claim space with a compare and swap on a shared reserve cursor, copy their message in
+/// and then publish its header word. The consumer only reads a record once its header is committed,
+/// so a slow producer holds back the messages behind it, but never hands out a half written one.
+/// Cursors count bytes since the ring was created and never wrap, the whole buffer is usable.
+/// Records are 8 byte aligned and never wrap the end of the buffer.
+#[derive(Debug, Clone)]
+pub struct MpscProducer<'a> {
+    control : &'a Control,
+    data : *mut u8,
+    capacity : usize,
+    _buffer : PhantomData<&'a [u8]>,
+}
+
+/// The single consumer of an mpsc ring, see [`MpscProducer`]
+#[derive(Debug)]
+pub struct MpscConsumer<'a> {
+    control : &'a Control,
+    data : *mut u8,
+    capacity : usize,
+    _buffer : PhantomData<&'a mut [u8]>,
+}
+
+// Producers only write to space they claimed, and the consumer only to space it consumed
+unsafe impl Send for MpscProducer<'_> {}
+unsafe impl Sync for MpscProducer<'_> {}
+unsafe impl Send for MpscConsumer<'_> {}
+
+#[repr(C)]
+#[derive(Debug)]
+struct Control {
+    reserve : AtomicU64,
+    head : AtomicU64,
+}
+
+/// # Safety
+///
+/// `data` must point to `size` bytes that are aligned to 8 and start out zeroed.
+/// Every handle for the same ring must be created with the same `size`.
+unsafe fn layout<'a>(size : usize, data : *mut u8) -> (&'a Control, *mut u8, usize) {
+    if data.is_null() {panic!("data cannot be null")}
+    assert_eq!(0, data as usize % HEADER_SIZE, "data must be aligned to 8");
+    let capacity = size.saturating_sub(MPSC_CONTROL_SIZE) / HEADER_SIZE * HEADER_SIZE;
+    assert!(capacity >= 2 * HEADER_SIZE, "an mpsc ring needs room for at least 16 bytes");
+    unsafe { (&*(data as *const Control), data.add(MPSC_CONTROL_SIZE), capacity) }
+}
+
+/// The number of bytes a message takes up, its header included
+fn record_len(msg_len : usize) -> usize {
+    (HEADER_SIZE + msg_len).next_multiple_of(HEADER_SIZE)
+}
+
+impl<'a> MpscProducer<'a> {
+    /// # Safety
+    ///
+    /// `data` must point to `size` bytes that are aligned to 8 and were zeroed before the first handle was made.
+    /// Every handle for the same ring must be created with the same `size`.
+    pub unsafe fn new(size : usize, data : *mut u8) -> Self {
+        let (control, data, capacity) = unsafe { layout(size, data) };
+        Self { control, data, capacity, _buffer: PhantomData }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.capacity
+    }
+
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, or 0 if it did not fit
+    pub fn push(&self, msg : &[u8]) -> usize {
+        self.try_push(msg).unwrap_or(0)
+    }
+
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, including its header
+    pub fn try_push(&self, msg : &[u8]) -> Result<usize, RingError> {
+        let record = record_len(msg.len());
+        if record > self.capacity {
+            return Err(RingError::MessageTooLarge { len: msg.len(), max: self.capacity - HEADER_SIZE });
+        }
+
+        let (start, padding) = loop {
+            let reserve = self.control.reserve.load(Ordering::Relaxed);
+            let head = self.control.head.load(Ordering::Acquire);
+            let bytes_until_end = self.capacity - (reserve as usize % self.capacity);
+            //a record that does not fit before the end claims the rest of the buffer as padding too
+            let padding = if record <= bytes_until_end {0} else {bytes_until_end};
+
+            let limit = head + self.capacity as u64;
+            if reserve + (padding + record) as u64 > limit {
+                if padding == 0 || reserve + padding as u64 > limit {return Err(RingError::Full);}
+                //claim just the padding, once the consumer skips it the record can start over at the beginning
+                if self.control.reserve
+                    .compare_exchange_weak(reserve, reserve + padding as u64, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok() {
+                    self.header(reserve as usize % self.capacity).store(COMMITTED | PADDING, Ordering::Release);
+                    return Err(RingError::Full);
+                }
+                continue;
+            }
+            if self.control.reserve
+                .compare_exchange_weak(reserve, reserve + (padding + record) as u64, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok() {
+                break (reserve + padding as u64, padding);
+            }
+        };
+
+        if padding != 0 {
+            self.header((start - padding as u64) as usize % self.capacity).store(COMMITTED | PADDING, Ordering::Release);
+        }
+        let offset = start as usize % self.capacity;
+        unsafe { ptr::copy_nonoverlapping(msg.as_ptr(), self.data.add(offset + HEADER_SIZE), msg.len()) };
+        self.header(offset).store(COMMITTED | msg.len() as u64, Ordering::Release);
+        Ok(record)
+    }
+
+    fn header(&self, offset : usize) -> &AtomicU64 {
+        unsafe { &*(self.data.add(offset) as *const AtomicU64) }
+    }
+}
+
+impl<'a> MpscConsumer<'a> {
+    /// # Safety
+    ///
+    /// Same as [`MpscProducer::new`], and there must only be one consumer per ring
+    pub unsafe fn new(size : usize, data : *mut u8) -> Self {
+        let (control, data, capacity) = unsafe { layout(size, data) };
+        Self { control, data, capacity, _buffer: PhantomData }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_head(&self) -> u64 {
+        self.control.head.load(Ordering::Relaxed)
+    }
+
+    pub fn get_reserve(&self) -> u64 {
+        self.control.reserve.load(Ordering::Relaxed)
+    }
+
+    /// True when nothing has been reserved past the head. A message that is still being written
+    /// makes this false even though [`MpscConsumer::try_pop`] cannot return it yet.
+    pub fn is_empty(&self) -> bool {
+        self.get_head() == self.control.reserve.load(Ordering::Acquire)
+    }
+
+    /// Pops the next message into `buffer` and returns its length, or 0 if there was nothing to pop
+    pub fn pop(&mut self, buffer : &mut [u8]) -> usize {
+        match self.try_pop(buffer) {
+            Ok(msg_len) => msg_len,
+            Err(e @ RingError::Corrupted { .. }) => panic!("Error: {e}"),
+            Err(_) => 0,
+        }
+    }
+
+    /// Pops the next message into `buffer` and returns its length.
+    /// Returns [`RingError::Empty`] while the next message is not committed yet, even if later ones are.
+    pub fn try_pop(&mut self, buffer : &mut [u8]) -> Result<usize, RingError> {
+        loop {
+            let head = self.control.head.load(Ordering::Relaxed);
+            let offset = head as usize % self.capacity;
+            let header = self.header(offset).load(Ordering::Acquire);
+            if header & COMMITTED == 0 {return Err(RingError::Empty);}
+
+            let bytes_until_end = self.capacity - offset;
+            if header & PADDING != 0 {
+                //the rest of the padding was zeroed when it was last consumed, only its header was written
+                self.header(offset).store(0, Ordering::Relaxed);
+                self.control.head.store(head + bytes_until_end as u64, Ordering::Release);
+                continue;
+            }
+
+            let msg_len = (header & LEN_MASK) as usize;
+            let record = record_len(msg_len);
+            if record > bytes_until_end {
+                let tail = self.control.reserve.load(Ordering::Relaxed) as usize % self.capacity;
+                return Err(RingError::Corrupted { head: offset, tail, len: msg_len });
+            }
+            if msg_len > buffer.len() {
+                return Err(RingError::DestinationTooSmall { needed: msg_len, available: buffer.len() });
+            }
+
+            unsafe {
+                ptr::copy_nonoverlapping(self.data.add(offset + HEADER_SIZE), buffer.as_mut_ptr(), msg_len);
+                //producers expect zeroes, a stale length anywhere in here could be taken for a commit later
+                ptr::write_bytes(self.data.add(offset), 0, record);
+            }
+            self.control.head.store(head + record as u64, Ordering::Release);
+            return Ok(msg_len);
+        }
+    }
+
+    fn header(&self, offset : usize) -> &AtomicU64 {
+        unsafe { &*(self.data.add(offset) as *const AtomicU64) }
+    }
+}
+
+impl Display for MpscConsumer<'_> {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error>{
+        write!(format, "\nMpsc Ring Buffer: reserve: {}, head: {}, size: {}\n",
+                self.get_reserve(),
+                self.get_head(),
+                self.capacity)
+    }
+}
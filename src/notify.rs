@@ -0,0 +1,124 @@
+use std::{io, mem::size_of, os::{fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd}, unix::net::UnixStream}, ptr};
+
+/// An eventfd the producer signals when it publishes data to a consumer that announced it is idle,
+/// see [`crate::ringbuffer_rw::RingbufRw::set_notifier`] and [`crate::ringbuffer_ro::RingbufRo::announce_idle`].
+/// The consumer adds it to its epoll set, it becomes readable once there is something to pop.
+#[derive(Debug)]
+pub struct Notifier {
+    fd : OwnedFd,
+}
+
+impl Notifier {
+    /// Creates a fresh eventfd. It is non blocking, so [`Notifier::drain`] never stalls an event loop.
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {return Err(io::Error::last_os_error());}
+        Ok(Self { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// Another handle to the same eventfd, e.g. to keep one after handing the original to a ring
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self { fd: self.fd.try_clone()? })
+    }
+
+    /// Wakes whoever is polling the eventfd
+    pub fn notify(&self) -> io::Result<()> {
+        let one = 1u64;
+        let written = unsafe { libc::write(self.fd.as_raw_fd(), &one as *const u64 as *const libc::c_void, size_of::<u64>()) };
+        if written < 0 {
+            let e = io::Error::last_os_error();
+            //the counter is saturated, which means the consumer is already going to wake up
+            if e.kind() != io::ErrorKind::WouldBlock {return Err(e);}
+        }
+        Ok(())
+    }
+
+    /// Resets the eventfd after it polled readable and returns how many times it was signalled, 0 if it was not
+    pub fn drain(&self) -> io::Result<u64> {
+        let mut count = 0u64;
+        let read = unsafe { libc::read(self.fd.as_raw_fd(), &mut count as *mut u64 as *mut libc::c_void, size_of::<u64>()) };
+        if read < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {return Ok(0);}
+            return Err(e);
+        }
+        Ok(count)
+    }
+
+    /// Hands a copy of the eventfd to the process on the other end of `socket`
+    pub fn send(&self, socket : &UnixStream) -> io::Result<()> {
+        send_fd(socket, self.fd.as_raw_fd())
+    }
+
+    /// Receives an eventfd sent with [`Notifier::send`]
+    pub fn recv(socket : &UnixStream) -> io::Result<Self> {
+        Ok(Self { fd: recv_fd(socket)? })
+    }
+}
+
+impl AsRawFd for Notifier {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for Notifier {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+//room for a cmsghdr carrying one fd, u64s so it is aligned the way CMSG_* expect
+const CMSG_WORDS: usize = 4;
+
+/// Sends `fd` as SCM_RIGHTS along with a single byte, since some systems drop control data without any payload
+fn send_fd(socket : &UnixStream, fd : RawFd) -> io::Result<()> {
+    let mut byte = 0u8;
+    let mut iov = libc::iovec { iov_base: &mut byte as *mut u8 as *mut libc::c_void, iov_len: 1 };
+    let mut control = [0u64; CMSG_WORDS];
+    let space = unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as u32) } as usize;
+    debug_assert!(space <= size_of::<[u64; CMSG_WORDS]>());
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space as _;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+    }
+
+    if unsafe { libc::sendmsg(socket.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn recv_fd(socket : &UnixStream) -> io::Result<OwnedFd> {
+    let mut byte = 0u8;
+    let mut iov = libc::iovec { iov_base: &mut byte as *mut u8 as *mut libc::c_void, iov_len: 1 };
+    let mut control = [0u64; CMSG_WORDS];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = size_of::<[u64; CMSG_WORDS]>() as _;
+
+    let received = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if received < 0 {return Err(io::Error::last_os_error());}
+    if received == 0 {return Err(io::ErrorKind::UnexpectedEof.into());}
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_level != libc::SOL_SOCKET || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no file descriptor was sent"));
+        }
+        let fd = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd);
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
@@ -1,206 +1,469 @@
 use core::slice;
-use std::{mem::size_of, fmt::{Display, Formatter}};
-use crate::SZ_OF_USIZE;
-
-#[cfg(feature = "avx2")]
-use crate::avx::SliceExt;
+use std::{cell::Cell, fmt::{Display, Formatter}, sync::atomic::{AtomicU64, Ordering, fence}, time::Duration};
+use crate::{CACHE_LINE, CONTROL_SIZE, PAD_MARKER, LenWidth, copy_bytes, record_len, drain_and_fill::{Parts, calc_curr_bytes, get_parts, offset, peek}, error::RingError, futex::{self, Waiters}};
 
+/// The consumer half of the ring. It owns `head` and only ever reads `tail`,
+/// which is published by the producer with release ordering. Both are positions that only ever
+/// grow and are taken modulo the size of the buffer to find their place in it.
 #[derive(Debug)]
 pub struct RingbufRo<'a> {
-    pub(crate) head : &'a mut usize,
-    pub(crate) tail : &'a usize,
+    pub(crate) head : &'a AtomicU64,
+    pub(crate) tail : &'a AtomicU64,
+    pub(crate) waiters : &'a Waiters,
+    //the buffer, followed by a second mapping of it in mirrored mode
     pub(crate) buffer : &'a [u8],
+    //the size of the buffer without its mirror, which positions are taken modulo
+    size : usize,
+    pub(crate) contiguous : bool,
+    pub(crate) lossy : bool,
+    pub(crate) len_width : LenWidth,
+    //the last tail read from the producer's cache line
+    cached_tail : Cell<Option<u64>>,
 }
 
 impl <'a> RingbufRo<'a> {
-    pub fn make(tail : & 'a usize, head : & 'a mut usize, buffer : & 'a [u8]) -> Self {
-        Self { tail, head, buffer }
+    pub fn make(tail : & 'a AtomicU64, head : & 'a AtomicU64, waiters : & 'a Waiters, buffer : & 'a [u8]) -> Self {
+        Self { tail, head, waiters, buffer, size: buffer.len(), contiguous: false, lossy: false, len_width: LenWidth::default(), cached_tail: Cell::new(None) }
     }
 
     /// # Safety
     ///
     /// This function is used to create a ringbuffer from a pointer and length
-    /// It is up to the caller to ensure the size argument is correct 
+    /// It is up to the caller to ensure the size argument is correct,
+    /// [`crate::header::SegmentHeader::attach`] can check it for segments that carry a header
     pub unsafe fn new(size : usize, data : * mut u8) -> Self {
         if data.is_null() {panic!("data cannot be null")}
-        let tail : & usize = unsafe { &*(data as * mut usize) };
-        let data = unsafe { data.add(SZ_OF_USIZE) };
-        let head : & mut usize = unsafe { &mut *(data as * mut usize) };
-        let data = unsafe { data.add(SZ_OF_USIZE) };
-        let size = size - (size_of::<usize>() * 2);
-        return RingbufRo::make(tail, head, unsafe {slice::from_raw_parts_mut(data, size)} );
+        let tail : & AtomicU64 = unsafe { &*(data as * const AtomicU64) };
+        //the head and the waiters get cache lines of their own, so the two halves do not keep stealing each other's
+        let head : & AtomicU64 = unsafe { &*(data.add(CACHE_LINE) as * const AtomicU64) };
+        let waiters : & Waiters = unsafe { &*(data.add(2 * CACHE_LINE) as * const Waiters) };
+        let data = unsafe { data.add(CONTROL_SIZE) };
+        let size = size - CONTROL_SIZE;
+        RingbufRo::make(tail, head, waiters, unsafe {slice::from_raw_parts(data, size)} )
+    }
+
+    /// # Safety
+    ///
+    /// Like [`RingbufRo::new`], but panics unless the buffer is a power of two bytes, see [`crate::ringbuffer_rw::RingbufRw::new_pow2`]
+    pub unsafe fn new_pow2(size : usize, data : * mut u8) -> Self {
+        assert!(size.saturating_sub(CONTROL_SIZE).is_power_of_two(), "the buffer must be a power of two bytes");
+        unsafe { RingbufRo::new(size, data) }
+    }
+
+    /// # Safety
+    ///
+    /// Like [`RingbufRo::new`], for a buffer that is mapped a second time right after itself,
+    /// see [`crate::ringbuffer_rw::RingbufRw::new_mirrored`]
+    pub unsafe fn new_mirrored(size : usize, data : * mut u8) -> Self {
+        let mut ring = unsafe { RingbufRo::new(size, data) };
+        ring.buffer = unsafe { slice::from_raw_parts(ring.buffer.as_ptr(), 2 * ring.size) };
+        ring
     }
 
     pub fn is_empty(&self) -> bool {
-        *self.head == *self.tail
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
     }
 
+    /// Every byte of the buffer holds data that has not been consumed yet
     pub fn is_full(&self) -> bool {
-        (*self.tail + 1) % self.buffer.len() == *self.head 
+        self.get_curr_bytes() == self.size
     }
 
     //this function returns the current number of bytes that are in the ring buffer
     pub fn get_curr_bytes(&self) -> usize {
-        let head = *self.head;
-        let tail = *self.tail;
-        if tail > head {
-            tail - head
-        } else if tail < head {
-            self.buffer.len() + tail - head
-        } else {
-            0
-        }
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        calc_curr_bytes(head, tail)
     }
 
-    pub fn get_head(&self) -> usize {
-        *self.head
+    /// The number of bytes ever consumed, length fields and padding included,
+    /// along with any the producer dropped in lossy mode
+    pub fn get_head(&self) -> u64 {
+        self.head.load(Ordering::Relaxed)
     }
     
-    pub fn set_head(&mut self, num: usize) {
-        *self.head = num;
+    pub fn set_head(&mut self, num: u64) {
+        self.cached_tail.set(None);
+        self.head.store(num, Ordering::Release);
     }
     
-    pub fn get_tail(&self) -> usize {
-        *self.tail
+    /// The number of bytes the producer has ever published
+    pub fn get_tail(&self) -> u64 {
+        self.tail.load(Ordering::Acquire)
     }
 
     pub fn get_size(&self) -> usize {
-        self.buffer.len()
+        self.size
+    }
+
+    /// Whether the buffer is mapped twice, so every message can be read in one part
+    pub fn is_mirrored(&self) -> bool {
+        self.buffer.len() > self.size
     }
 
 
     pub fn empty_slots_left(&self) -> usize {
-        self.buffer.len() - self.get_curr_bytes() - 1
+        self.size - self.get_curr_bytes()
     }
 
-    #[cfg(feature = "avx2")]
+    /// Must match [`crate::ringbuffer_rw::RingbufRw::set_contiguous`] on the producer's side
+    pub fn set_contiguous(&mut self, contiguous: bool) {
+        self.contiguous = contiguous;
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        self.contiguous
+    }
+
+    /// Must match [`crate::ringbuffer_rw::RingbufRw::set_len_width`] on the producer's side
+    pub fn set_len_width(&mut self, len_width: LenWidth) {
+        self.len_width = len_width;
+    }
+
+    pub fn get_len_width(&self) -> LenWidth {
+        self.len_width
+    }
+
+    /// Must match [`crate::ringbuffer_rw::RingbufRw::set_lossy`] on the producer's side.
+    /// The head is then only advanced with a compare and swap, and a message the producer
+    /// dropped while it was being copied out is thrown away and the next one is read instead.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// Pops the next message into `buffer` and returns its length, or 0 if there was nothing to pop.
+    /// A zero length message also returns 0, use [`RingbufRo::try_pop`] to tell it apart from an empty ring.
+    /// If `buffer` is too small the message is left in the ring and 0 is returned,
+    /// [`RingbufRo::peek_len`] tells you how big it needs to be.
+    /// Panics if the ring is corrupt, use [`RingbufRo::try_pop`] to recover from that instead.
     pub fn pop(&mut self, buffer: &mut [u8]) -> usize{
-        //if buffer is empty or there arent enough bytes to fill the msg_len
-        let curr_bytes = self.get_curr_bytes();
-
-        if self.is_empty() || curr_bytes < SZ_OF_USIZE {return 0;}
-
-        let head = *self.head;
-
-//the msg_len field is wrapping
-        if head + SZ_OF_USIZE > self.buffer.len() { 
-//EXTRA SAD CASE
-            let bytes_until_end = self.buffer.len() - head;
-            let first_half = &self.buffer[head..];
-            let second_half = &self.buffer[..SZ_OF_USIZE-bytes_until_end];
-            let mut msg_len_bytes: [u8;size_of::<usize>()] = [0;size_of::<usize>()];
-            msg_len_bytes[..first_half.len()].copy_from_slice(first_half);
-            msg_len_bytes[first_half.len()..].copy_from_slice(second_half);
-            let msg_len = usize::from_le_bytes(msg_len_bytes);
-            if msg_len <= curr_bytes - SZ_OF_USIZE { //we've already wrapped so we dont have to worry about the msg wrapping
-                buffer[..msg_len].copy_from_slice_avx(&self.buffer[SZ_OF_USIZE-bytes_until_end..msg_len+SZ_OF_USIZE-bytes_until_end]);
-                *self.head = msg_len + SZ_OF_USIZE - bytes_until_end;
-                msg_len
-            } else {
-                panic!("Error: the msg_len is greater than the number of bytes available");
-            } 
-        } else {
-            //there are at least enough bytes to get the msg_len field
-            let msg_len_slice = &self.buffer[head..head+SZ_OF_USIZE];
-            let msg_len= usize::from_le_bytes(msg_len_slice.try_into().unwrap());
-
-            if msg_len <= curr_bytes - SZ_OF_USIZE {
-                let bytes_until_end = self.buffer.len() - head;
-
-                if msg_len > bytes_until_end - SZ_OF_USIZE { //does the message wrap the buffer
-//SAD CASE
-                    let first_half = &self.buffer[head+SZ_OF_USIZE..];
-                    let second_half = &self.buffer[..msg_len+SZ_OF_USIZE-bytes_until_end];
-                    buffer[..first_half.len()].copy_from_slice_avx(first_half);
-                    buffer[first_half.len()..first_half.len()+second_half.len()].copy_from_slice_avx(second_half);
-                    *self.head = msg_len+SZ_OF_USIZE-bytes_until_end;
-
-                    msg_len
-                } else {
-//HAPPY CASE
-                    buffer[..msg_len].copy_from_slice_avx(&self.buffer[head+SZ_OF_USIZE..head+SZ_OF_USIZE+msg_len]);
-                    *self.head = (head + SZ_OF_USIZE + msg_len) % self.buffer.len();
-                    msg_len
+        match self.try_pop(buffer) {
+            Ok(msg_len) => msg_len,
+            Err(e @ RingError::Corrupted { .. }) => panic!("Error: {e}"),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the payload length of the next message without consuming it
+    pub fn peek_len(&self) -> Result<usize, RingError> {
+        self.next_len().map(|(_, msg_len)| msg_len)
+    }
+
+    /// Pops the next message into `buffer` and returns its length, which is `Ok(0)` for a zero length message.
+    /// The head is only advanced when a whole message was copied out.
+    pub fn try_pop(&mut self, buffer: &mut [u8]) -> Result<usize, RingError> {
+        loop {
+            let ((first_part, second_part), head, new_head) = self.next_msg()?;
+            let msg_len = first_part.len() + second_part.map_or(0, |part| part.len());
+
+            if msg_len > buffer.len() {
+                return Err(RingError::DestinationTooSmall { needed: msg_len, available: buffer.len() });
+            }
+
+            copy_bytes(&mut buffer[..first_part.len()], first_part);
+            if let Some(second_part) = second_part {
+                copy_bytes(&mut buffer[first_part.len()..msg_len], second_part);
+            }
+
+            //in lossy mode the producer may have dropped the message while we copied it
+            if self.advance_head(head, new_head) {return Ok(msg_len);}
+        }
+    }
+
+    /// Pops the next message into `buffer`, growing it to fit, and returns its length. This is how a lossy
+    /// ring is read when the message is needed whole, since a borrowed one could be overwritten while it is copied.
+    pub(crate) fn pop_into_vec(&mut self, buffer: &mut Vec<u8>) -> Result<usize, RingError> {
+        loop {
+            let msg_len = self.peek_len()?;
+            if buffer.len() < msg_len {buffer.resize(msg_len, 0);}
+            match self.try_pop(buffer) {
+                //the message we peeked at was dropped and the next one is longer
+                Err(RingError::DestinationTooSmall { .. }) => {}
+                popped => return popped,
+            }
+        }
+    }
+
+    /// Pops as many messages as fit into `arena` and `offsets` and returns how many that was, or 0 if there was nothing to pop.
+    /// See [`RingbufRo::try_pop_batch`], which also tells an empty ring apart from a first message that does not fit.
+    /// Panics if the ring is corrupt.
+    pub fn pop_batch(&mut self, arena: &mut [u8], offsets: &mut [usize]) -> usize {
+        match self.try_pop_batch(arena, offsets) {
+            Ok(count) => count,
+            Err(e @ RingError::Corrupted { .. }) => panic!("Error: {e}"),
+            Err(_) => 0,
+        }
+    }
+
+    /// Pops up to `offsets.len()` messages, copying their payloads back to back into `arena`, and advances
+    /// the head once for all of them. `offsets[i]` is set to where message `i` ends, so it spans
+    /// `offsets[i-1]..offsets[i]`, starting at 0 for the first one. Stops at the first message that would
+    /// go past the end of `arena`, which is left in the ring, so the length of `arena` is the byte budget.
+    /// Returns [`RingError::DestinationTooSmall`] if not even the first message fits.
+    pub fn try_pop_batch(&mut self, arena: &mut [u8], offsets: &mut [usize]) -> Result<usize, RingError> {
+        if offsets.is_empty() {return Ok(0);}
+        'retry: loop {
+            let tail = self.load_tail();
+            let head = self.skip_padding(tail);
+            let mut phantom_head = head;
+            let mut count = 0;
+            let mut used = 0;
+
+            while count < offsets.len() && phantom_head != tail {
+                //padding in the middle of the batch is skipped along with the messages
+                if self.contiguous && self.is_padding(phantom_head) {
+                    phantom_head += self.bytes_until_end(phantom_head) as u64;
+                    continue;
+                }
+                let msg_len = match peek(phantom_head, tail, self.len_width, self.ring()) {
+                    Ok(msg_len) => msg_len,
+                    //a corrupt message after the first one is left for the next call to report
+                    Err(_) if count > 0 => break,
+                    //a lossy producer may be overwriting a length it already dropped, look again at the new head
+                    Err(RingError::Corrupted { .. }) if self.lossy && self.was_dropped(head, tail) => continue 'retry,
+                    Err(e) => return Err(e),
+                };
+                if used + msg_len > arena.len() {
+                    if count == 0 {return Err(RingError::DestinationTooSmall { needed: msg_len, available: arena.len() });}
+                    break;
+                }
+
+                let payload = self.offset(phantom_head + self.len_width.bytes() as u64);
+                let (first_part, second_part) = self.parts(msg_len, payload);
+                copy_bytes(&mut arena[used..used + first_part.len()], first_part);
+                if let Some(second_part) = second_part {
+                    copy_bytes(&mut arena[used + first_part.len()..used + msg_len], second_part);
                 }
-            }else { //there were not enough bytes to fulfil the msg_len, this should never happen
-                panic!("Error: not enough bytes to fill msg_len");
+                used += msg_len;
+                offsets[count] = used;
+                count += 1;
+                phantom_head += record_len(msg_len, self.len_width, self.contiguous) as u64;
+            }
+
+            if count == 0 {return Err(RingError::Empty);}
+            //in lossy mode the producer may have dropped some of the messages while we copied them
+            if self.advance_head(head, phantom_head) {return Ok(count);}
+        }
+    }
+
+    /// Like [`RingbufRo::try_pop`], but when the ring is empty it sleeps until the producer pushes something.
+    /// Returns [`RingError::Empty`] if nothing arrived within `timeout`, `None` waits forever.
+    pub fn pop_blocking(&mut self, buffer: &mut [u8], timeout: Option<Duration>) -> Result<usize, RingError> {
+        let waiters = self.waiters;
+        futex::block_on(&waiters.data, timeout, RingError::Empty, || self.try_pop(buffer))
+    }
+
+    /// Tells the producer this consumer is going to sleep, so its next push signals the
+    /// [`crate::notify::Notifier`] it was given. Returns false when a message arrived in the meantime,
+    /// in which case keep popping instead of waiting on the notifier.
+    pub fn announce_idle(&self) -> bool {
+        futex::announce(&self.waiters.data, || self.is_empty())
+    }
+
+    /// Borrows the next message in place instead of copying it out.
+    /// The head is advanced past it when the returned guard is committed or dropped.
+    /// In lossy mode the producer can overwrite a borrowed message, which only [`ReadGuard::commit`]
+    /// reports, so check its result before trusting the bytes or use [`RingbufRo::try_pop`] instead.
+    pub fn read(&mut self) -> Result<ReadGuard<'_, 'a>, RingError> {
+        let ((first_part, second_part), head, new_head) = self.next_msg()?;
+        Ok(ReadGuard { ring: self, first_part, second_part, head, new_head, consumed: false })
+    }
+
+    /// Finds the payload of the next message, in two parts if it wraps the end of the buffer,
+    /// along with the head it starts at and where the head goes once it has been consumed
+    fn next_msg(&self) -> Result<(Parts<'a>, u64, u64), RingError> {
+        let (head, msg_len) = self.next_len()?;
+        let payload = self.offset(head + self.len_width.bytes() as u64);
+        let (first_part, second_part) = self.parts(msg_len, payload);
+        let record = record_len(msg_len, self.len_width, self.contiguous);
+        Ok(((first_part, second_part), head, head + record as u64))
+    }
+
+    /// Finds the head the next message starts at and the length of its payload
+    fn next_len(&self) -> Result<(u64, usize), RingError> {
+        loop {
+            let tail = self.load_tail();
+            let head = self.skip_padding(tail);
+            match peek(head, tail, self.len_width, self.ring()) {
+                //a lossy producer may be overwriting a length it already dropped, or have dropped everything
+                //up to a tail newer than ours, look again at the new head
+                Err(RingError::Corrupted { .. }) if self.lossy && self.was_dropped(head, tail) => {}
+                msg_len => return Ok((head, msg_len?)),
             }
         }
     }
 
+    /// In lossy mode, whether the producer dropped the message at `head` while we were reading it,
+    /// or had already dropped everything up to a tail newer than `tail`
+    fn was_dropped(&self, head: u64, tail: u64) -> bool {
+        //pairs with the fence in RingbufRw::drop_oldest, orders the reads of the message before the check
+        fence(Ordering::Acquire);
+        self.head.load(Ordering::Relaxed) != head || head > tail
+    }
 
-    #[cfg(not(feature = "avx2"))]
-    pub fn pop(&mut self, buffer: &mut [u8]) -> usize{
-        //if buffer is empty or there arent enough bytes to fill the msg_len
+    /// In contiguous mode, moves the head on to the start of the buffer when the producer
+    /// skipped the rest of it, and returns where the next message starts
+    fn skip_padding(&self, tail: u64) -> u64 {
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            if !self.contiguous || head == tail {return head;}
+
+            if !self.is_padding(head) {return head;}
+            //the padding is not a message, so it is handed back to the producer right away
+            let new_head = head + self.bytes_until_end(head) as u64;
+            if self.advance_head(head, new_head) {return new_head;}
+        }
+    }
 
-        let curr_bytes = self.get_curr_bytes();
+    /// The producer's tail. Its cache line is only read once everything up to the copy from last time
+    /// has been consumed, or every time in lossy mode, where the producer can move the head past that copy.
+    fn load_tail(&self) -> u64 {
+        if let Some(tail) = self.cached_tail.get() {
+            if !self.lossy && tail != self.head.load(Ordering::Relaxed) {return tail;}
+        }
+        self.refresh_tail()
+    }
 
-        if self.is_empty() || curr_bytes < SZ_OF_USIZE {return 0;}
+    /// Reads the producer's tail and keeps a copy of it
+    pub(crate) fn refresh_tail(&self) -> u64 {
+        //acquire so every byte up to the tail is visible
+        let tail = self.tail.load(Ordering::Acquire);
+        self.cached_tail.set(Some(tail));
+        tail
+    }
 
-        let head = *self.head;
+    /// In contiguous mode, whether the producer skipped the rest of the buffer from `head` on
+    fn is_padding(&self, head: u64) -> bool {
+        let bytes_until_end = self.bytes_until_end(head);
+        bytes_until_end < self.len_width.bytes() || self.len_width.decode(&self.buffer[self.offset(head)..]) == PAD_MARKER
+    }
 
-//the msg_len field is wrapping
-        if head + SZ_OF_USIZE > self.buffer.len() { 
-//EXTRA SAD CASE
-            let bytes_until_end = self.buffer.len() - head;
-            let first_half = &self.buffer[head..];
-            let second_half = &self.buffer[..SZ_OF_USIZE-bytes_until_end];
-            let mut msg_len_bytes: [u8;SZ_OF_USIZE] = [0;SZ_OF_USIZE];
-            msg_len_bytes[..first_half.len()].copy_from_slice(first_half);
-            msg_len_bytes[first_half.len()..].copy_from_slice(second_half);
-            let msg_len = usize::from_le_bytes(msg_len_bytes);
+    /// Where `pos` falls in the buffer
+    pub(crate) fn offset(&self, pos: u64) -> usize {
+        offset(pos, self.size)
+    }
 
-            if msg_len <= curr_bytes - SZ_OF_USIZE { //we've already wrapped so we dont have to worry about the msg wrapping
-                buffer[..msg_len].copy_from_slice(&self.buffer[SZ_OF_USIZE-bytes_until_end..msg_len+SZ_OF_USIZE-bytes_until_end]);
-                *self.head = msg_len + SZ_OF_USIZE - bytes_until_end;
-                msg_len
-            } else {
-                panic!("Error: the msg_len is greater than the number of bytes available");
-            } 
+    fn bytes_until_end(&self, pos: u64) -> usize {
+        self.size - self.offset(pos)
+    }
+
+    /// The buffer without its mirror
+    pub(crate) fn ring(&self) -> &'a [u8] {
+        &self.buffer[..self.size]
+    }
+
+    /// The `len` bytes at `offset`, in two parts if they wrap the end of a buffer that is not mirrored
+    pub(crate) fn parts(&self, len: usize, offset: usize) -> Parts<'a> {
+        if self.is_mirrored() {
+            (&self.buffer[offset..offset + len], None)
         } else {
-            //there are at least enough bytes to get the msg_len field
-            let msg_len_slice = &self.buffer[head..head+SZ_OF_USIZE];
-            let msg_len= usize::from_le_bytes(msg_len_slice.try_into().unwrap());
-
-            if msg_len <= curr_bytes - SZ_OF_USIZE {
-                let bytes_until_end = self.buffer.len() - head;
-
-                if msg_len > bytes_until_end - SZ_OF_USIZE { //does the message wrap the buffer
-//SAD CASE
-                    let first_half = &self.buffer[head+SZ_OF_USIZE..];
-                    let second_half = &self.buffer[..msg_len+SZ_OF_USIZE-bytes_until_end];
-                    buffer[..first_half.len()].copy_from_slice(first_half);
-                    buffer[first_half.len()..first_half.len()+second_half.len()].copy_from_slice(second_half);
-                    *self.head = msg_len+SZ_OF_USIZE-bytes_until_end;
-
-                    msg_len
-                } else {
-//HAPPY CASE
-                    buffer[..msg_len].copy_from_slice(&self.buffer[head+SZ_OF_USIZE..head+SZ_OF_USIZE+msg_len]);
-                    *self.head = (head + SZ_OF_USIZE + msg_len) % self.buffer.len();
-                    msg_len
-                }
-            }else { //there were not enough bytes to fulfil the msg_len, this should never happen
-                panic!("Error: not enough bytes to fill msg_len");
+            get_parts(len, offset, self.buffer)
+        }
+    }
+
+    /// Moves the head from `head` to `new_head`. In lossy mode this fails when the producer
+    /// already moved it to drop the message, and the caller has to start over.
+    pub(crate) fn advance_head(&self, head: u64, new_head: u64) -> bool {
+        if !self.lossy {
+            self.head.store(new_head, Ordering::Release);
+        } else {
+            //pairs with the fence in RingbufRw::drop_oldest, the copy only counts if the head did not move under it
+            fence(Ordering::Acquire);
+            if self.head.compare_exchange(head, new_head, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                return false;
             }
         }
+        //wakes the producer if it was waiting for space
+        let _ = futex::wake(&self.waiters.space);
+        true
+    }
+}
+
+/// A message borrowed in place from a [`RingbufRo`], in two parts if it wraps the end of the buffer.
+/// The producer cannot reuse its bytes until the guard is committed or dropped.
+#[derive(Debug)]
+pub struct ReadGuard<'r, 'a> {
+    ring : &'r mut RingbufRo<'a>,
+    first_part : &'a [u8],
+    second_part : Option<&'a [u8]>,
+    head : u64,
+    new_head : u64,
+    consumed : bool,
+}
+
+impl<'r, 'a> ReadGuard<'r, 'a> {
+    /// The payload, the second part is only there if the message wraps the end of the buffer
+    pub fn parts(&self) -> (&[u8], Option<&[u8]>) {
+        (self.first_part, self.second_part)
+    }
+
+    pub fn len(&self) -> usize {
+        self.first_part.len() + self.second_part.map_or(0, |part| part.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The payload as a single slice, which is always the case for a contiguous or a mirrored ring
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        match self.second_part {
+            None => Some(self.first_part),
+            Some(_) => None,
+        }
     }
 
+    /// Copies the payload out, for when it is needed after the guard is gone
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(self.len());
+        msg.extend_from_slice(self.first_part);
+        if let Some(second_part) = self.second_part {
+            msg.extend_from_slice(second_part);
+        }
+        msg
+    }
 
+    /// Consumes the message, handing its bytes back to the producer. In lossy mode this returns
+    /// [`RingError::Lapped`] if the producer dropped the message while it was borrowed, in which case
+    /// anything read through the guard may have been overwritten and has to be thrown away.
+    pub fn commit(mut self) -> Result<(), RingError> {
+        self.consume()
+    }
+
+    fn consume(&mut self) -> Result<(), RingError> {
+        self.consumed = true;
+        if self.ring.advance_head(self.head, self.new_head) {return Ok(());}
+        let skipped = self.ring.head.load(Ordering::Acquire) - self.head;
+        Err(RingError::Lapped { skipped: skipped as usize })
+    }
+}
+
+impl<'r, 'a> Drop for ReadGuard<'r, 'a> {
+    fn drop(&mut self) {
+        //a dropped guard cannot report a lap, see commit
+        if !self.consumed {let _ = self.consume();}
+    }
 }
 
 impl<'a> Display for RingbufRo<'a> {
     fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error>{
-        let hex: String = self.buffer.iter().map(|&byte| format!("{: >5x}", byte)).collect::<Vec<String>>().join("|");
-        let headtail: String = self.buffer.iter().enumerate()
+        let hex: String = self.ring().iter().map(|&byte| format!("{: >5x}", byte)).collect::<Vec<String>>().join("|");
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let (head_offset, tail_offset) = (self.offset(head), self.offset(tail));
+        let headtail: String = self.ring().iter().enumerate()
         .map(|(i, &_byte)| {
             if self.is_empty() {
                 String::from("EMPTY")
             } else if self.is_full() {
                 String::from("FULLL")
-            } else if i == *self.head {
+            } else if i == head_offset {
                 String::from("HEAD^")
-            } else if i == *self.tail {
+            } else if i == tail_offset {
                 String::from("TAIL^")
             } else {
                 String::from("     ")
@@ -209,9 +472,9 @@ impl<'a> Display for RingbufRo<'a> {
         .collect::<Vec<String>>().join("|");
         
         write!(format, "\nRing Buffer: tail: {}, head: {}, size: {}\n [ {} ]\n [ {} ]\n",
-                self.tail,
-                self.head,
-                self.buffer.len(),
+                tail,
+                head,
+                self.size,
                 &hex,
                 &headtail)
     }
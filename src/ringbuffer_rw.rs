@@ -1,166 +1,476 @@
 use core::slice;
-use std::{mem::size_of, fmt::{Formatter, Display}};
-use crate::SZ_OF_USIZE;
-#[cfg(feature = "avx2")]
-use crate::avx::SliceExt;
+use std::{fmt::{Formatter, Display}, iter::Peekable, sync::atomic::{AtomicU64, Ordering, fence}, time::Duration};
+use crate::{CACHE_LINE, CONTROL_SIZE, PAD_MARKER, LenWidth, copy_bytes, record_len, error::RingError, drain_and_fill::{PartsMut, calc_curr_bytes, copy_in_parts, get_parts_mut, offset, peek}, futex::{self, Waiters}};
+#[cfg(target_os = "linux")]
+use crate::notify::Notifier;
 
+/// The producer half of the ring. It owns `tail` and only ever reads `head`,
+/// which is published by the consumer with release ordering. Both are positions that only ever
+/// grow and are taken modulo the size of the buffer to find their place in it.
 #[derive(Debug)]
 pub struct RingbufRw <'a> {
-    pub(crate) head : &'a usize,
-    pub(crate) tail : &'a mut usize,
+    pub(crate) head : &'a AtomicU64,
+    pub(crate) tail : &'a AtomicU64,
+    pub(crate) waiters : &'a Waiters,
+    //the buffer, followed by a second mapping of it in mirrored mode
     pub(crate) buffer : &'a mut [u8],
+    //the size of the buffer without its mirror, which positions are taken modulo
+    size : usize,
+    pub(crate) contiguous : bool,
+    pub(crate) len_width : LenWidth,
+    lossy : bool,
+    dropped : u64,
+    //the end of messages a batch has written but not published yet
+    unpublished : Option<u64>,
+    //the last head read from the consumer's cache line
+    cached_head : Option<u64>,
+    #[cfg(target_os = "linux")]
+    notifier : Option<Notifier>,
 }
 
 impl <'a> RingbufRw <'a> {
-    pub fn make(tail : & 'a mut usize, head : & 'a usize, buffer : & 'a mut [u8]) -> Self {
-        Self { tail, head, buffer }
+    pub fn make(tail : & 'a AtomicU64, head : & 'a AtomicU64, waiters : & 'a Waiters, buffer : & 'a mut [u8]) -> Self {
+        Self {
+            tail,
+            head,
+            waiters,
+            size: buffer.len(),
+            buffer,
+            contiguous: false,
+            len_width: LenWidth::default(),
+            lossy: false,
+            dropped: 0,
+            unpublished: None,
+            cached_head: None,
+            #[cfg(target_os = "linux")]
+            notifier: None,
+        }
     }
 
     /// # Safety
     ///
     /// This function is used to create a ringbuffer from a pointer and length
-    /// It is up to the caller to ensure the size argument is correct 
+    /// It is up to the caller to ensure the size argument is correct,
+    /// [`crate::header::SegmentHeader::attach`] can check it for segments that carry a header
     pub unsafe fn new(size : usize, data : * mut u8) -> Self {
         if data.is_null() {panic!("data cannot be null")}
-        let tail : &mut usize = unsafe { &mut *(data as * mut usize) };
-        let data = unsafe { data.add(SZ_OF_USIZE) };
-        let head : &usize = unsafe { &*(data as * mut usize) };
-        let data = unsafe { data.add(SZ_OF_USIZE) };
-        let size = size - (size_of::<usize>() * 2);
-        return RingbufRw::make(tail, head,unsafe {slice::from_raw_parts_mut(data, size)} );
+        let tail : &AtomicU64 = unsafe { &*(data as * const AtomicU64) };
+        //the head and the waiters get cache lines of their own, so the two halves do not keep stealing each other's
+        let head : &AtomicU64 = unsafe { &*(data.add(CACHE_LINE) as * const AtomicU64) };
+        let waiters : &Waiters = unsafe { &*(data.add(2 * CACHE_LINE) as * const Waiters) };
+        let data = unsafe { data.add(CONTROL_SIZE) };
+        let size = size - CONTROL_SIZE;
+        RingbufRw::make(tail, head, waiters, unsafe {slice::from_raw_parts_mut(data, size)} )
+    }
+
+    /// # Safety
+    ///
+    /// Like [`RingbufRw::new`], but panics unless the buffer after the [`CONTROL_SIZE`] control block is a power
+    /// of two bytes. Every ring of such a size turns positions into indexes with a mask instead of a division,
+    /// however it was made, this only makes sure the ring gets it. [`crate::shm::ShmRing::round_capacity`]
+    /// rounds a capacity up to one.
+    pub unsafe fn new_pow2(size : usize, data : * mut u8) -> Self {
+        assert!(size.saturating_sub(CONTROL_SIZE).is_power_of_two(), "the buffer must be a power of two bytes");
+        unsafe { RingbufRw::new(size, data) }
+    }
+
+    /// # Safety
+    ///
+    /// Like [`RingbufRw::new`], for a buffer that is mapped a second time right after itself, so `data`
+    /// must be followed by `2 * (size - CONTROL_SIZE)` bytes after the control block. Every message is then
+    /// written and read as a single slice, even where it wraps the end of the buffer.
+    /// [`crate::shm::ShmRing`] sets this up for segments created with [`crate::header::FLAG_MIRRORED`].
+    pub unsafe fn new_mirrored(size : usize, data : * mut u8) -> Self {
+        let mut ring = unsafe { RingbufRw::new(size, data) };
+        ring.buffer = unsafe { slice::from_raw_parts_mut(ring.buffer.as_mut_ptr(), 2 * ring.size) };
+        ring
     }
 
     pub fn is_empty(&self) -> bool {
-        *self.head == *self.tail
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
     }
 
+    /// Every byte of the buffer holds data that has not been consumed yet
     pub fn is_full(&self) -> bool {
-        (*self.tail + 1) % self.buffer.len() == *self.head 
+        self.get_curr_bytes() == self.size
     }
 
     pub fn get_curr_bytes(&self) -> usize {
-        let head = *self.head;
-        let tail = *self.tail;
-        if tail > head {
-            tail - head
-        } else if tail < head {
-            self.buffer.len() + tail - head
-        } else {
-            0
-        }
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        calc_curr_bytes(head, tail)
     }
     
-    pub fn get_head(&self) -> usize {
-        *self.head
+    /// The number of bytes the consumer has ever consumed, or the producer dropped in lossy mode
+    pub fn get_head(&self) -> u64 {
+        self.head.load(Ordering::Acquire)
     }
     
-    pub fn get_tail(&self) -> usize {
-        *self.tail
+    /// The number of bytes ever published, length fields and padding included
+    pub fn get_tail(&self) -> u64 {
+        self.tail.load(Ordering::Relaxed)
     }
 
-    pub fn set_tail(&mut self, num: usize) {
-        *self.tail = num;
+    pub fn set_tail(&mut self, num: u64) {
+        self.cached_head = None;
+        self.tail.store(num, Ordering::Release);
     }
 
     pub fn get_size(&self) -> usize {
-        self.buffer.len()
+        self.size
+    }
+
+    /// Whether the buffer is mapped twice, so every message can be written in one part
+    pub fn is_mirrored(&self) -> bool {
+        self.buffer.len() > self.size
     }
 
     pub fn empty_slots_left(&self) -> usize {
-        self.buffer.len() - self.get_curr_bytes() - 1
+        self.size - self.get_curr_bytes()
     }
-    #[cfg(feature = "avx2")]
+
+    /// In contiguous mode a message never wraps the end of the buffer. When it does not fit before the end,
+    /// the rest of the buffer is skipped with a [`crate::PAD_MARKER`] and the message is placed at the start.
+    /// Every length field is aligned to its width, so payloads can be cast in place.
+    /// Both halves of the ring must agree on the mode, and it can only be changed while the ring is empty.
+    pub fn set_contiguous(&mut self, contiguous: bool) {
+        self.contiguous = contiguous;
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        self.contiguous
+    }
+
+    /// The width of the length field written in front of every message, [`LenWidth::U64`] by default.
+    /// Narrower fields save bandwidth on small messages but limit how long a message can be.
+    /// Both halves of the ring must agree on the width, and it can only be changed while the ring is empty.
+    pub fn set_len_width(&mut self, len_width: LenWidth) {
+        self.len_width = len_width;
+    }
+
+    pub fn get_len_width(&self) -> LenWidth {
+        self.len_width
+    }
+
+    /// In lossy mode a message that does not fit makes room by dropping the oldest whole messages
+    /// instead of failing with [`RingError::Full`], for streams where fresh data matters more than old data.
+    /// The producer moves the head itself with a compare and swap, so both halves must agree on the mode.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// The number of messages dropped in lossy mode to make room for newer ones
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Signals `notifier` whenever something is published while the consumer is idle,
+    /// see [`crate::ringbuffer_ro::RingbufRo::announce_idle`]. A busy consumer costs no system calls.
+    #[cfg(target_os = "linux")]
+    pub fn set_notifier(&mut self, notifier: Option<Notifier>) {
+        self.notifier = notifier;
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn get_notifier(&self) -> Option<&Notifier> {
+        self.notifier.as_ref()
+    }
+
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, or 0 if it did not fit
     pub fn push(&mut self, msg: &[u8]) -> usize {
-        //is buffer full?
-        //is there room for the message
-        let msg_len = msg.len();
+        self.try_push(msg).unwrap_or(0)
+    }
 
-        if self.is_full() || msg_len + SZ_OF_USIZE > self.empty_slots_left() {return 0;}
+    /// Pushes `msg` and returns the number of bytes it took up in the ring, including its length field
+    pub fn try_push(&mut self, msg: &[u8]) -> Result<usize, RingError> {
+        let mut grant = self.reserve(msg.len())?;
+        grant.copy_from_slice(msg);
+        let record = grant.record_len();
+        grant.commit();
+        Ok(record)
+    }
 
-        let tail = *self.tail;
-        let msg_len_bytes = msg_len.to_le_bytes();
-        let msg_len_bytes_len = msg_len_bytes.len();
-        let bytes_until_end = self.buffer.len() - tail;
+    /// Pushes as many of `msgs` as fit, in order, and publishes them with a single tail update.
+    /// Returns how many were pushed, the first one that did not fit and everything after it are left out.
+    /// In lossy mode older messages are dropped to make room as usual, so every message is pushed,
+    /// but a batch bigger than the ring is published in more than one step.
+    pub fn push_batch(&mut self, msgs: &[&[u8]]) -> usize {
+        self.push_iter(&mut msgs.iter().peekable())
+    }
 
-        if bytes_until_end < SZ_OF_USIZE {
-//EXTRA SAD CASE
-            self.buffer[tail..].copy_from_slice_avx(&msg_len_bytes[..bytes_until_end]);
-            self.buffer[..(msg_len_bytes_len-bytes_until_end)].copy_from_slice_avx(&msg_len_bytes[bytes_until_end..]);
-        
-            self.buffer[(msg_len_bytes_len-bytes_until_end)..(msg_len_bytes_len-bytes_until_end)+msg_len].copy_from_slice_avx(msg);
-            
-            *self.tail = msg_len_bytes_len -bytes_until_end + msg_len;
-//SAD CASE
-        } else if bytes_until_end <= SZ_OF_USIZE + msg_len {
-            self.buffer[tail..tail+SZ_OF_USIZE].copy_from_slice_avx(&msg_len_bytes);
-
-            self.buffer[tail+SZ_OF_USIZE..].copy_from_slice_avx(&msg[..(bytes_until_end-SZ_OF_USIZE)]);
-            self.buffer[..msg_len+SZ_OF_USIZE-bytes_until_end].copy_from_slice_avx(&msg[(bytes_until_end-SZ_OF_USIZE)..]);
-
-            *self.tail = msg_len + SZ_OF_USIZE - bytes_until_end;
-//HAPPY CASE
+    /// Like [`RingbufRw::push_batch`], for messages that come from an iterator.
+    /// Each message is only taken once it has been pushed, so the first one that did not fit
+    /// is still next in `msgs` and the same iterator can be passed in again once there is room.
+    pub fn push_iter<I>(&mut self, msgs: &mut Peekable<I>) -> usize
+    where
+        I: Iterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut pushed = 0;
+        while let Some(msg) = msgs.peek() {
+            let msg = msg.as_ref();
+            let Ok(mut grant) = self.reserve(msg.len()) else { break };
+            grant.copy_from_slice(msg);
+            grant.write_len();
+            msgs.next();
+            pushed += 1;
+        }
+        if let Some(tail) = self.unpublished {
+            self.publish_tail(tail);
+        }
+        pushed
+    }
+
+    /// Like [`RingbufRw::try_push`], but when the ring is full it sleeps until the consumer makes room.
+    /// Returns [`RingError::Full`] if there still was no room after `timeout`, `None` waits forever.
+    pub fn push_blocking(&mut self, msg: &[u8], timeout: Option<Duration>) -> Result<usize, RingError> {
+        let waiters = self.waiters;
+        futex::block_on(&waiters.space, timeout, RingError::Full, || self.try_push(msg))
+    }
+
+    /// Publishes written bytes to the consumer, waking it if it was waiting for data or idle
+    pub(crate) fn publish_tail(&mut self, tail: u64) {
+        self.unpublished = None;
+        self.tail.store(tail, Ordering::Release);
+        let was_idle = futex::wake(&self.waiters.data);
+        #[cfg(target_os = "linux")]
+        if let (true, Some(notifier)) = (was_idle, &self.notifier) {
+            //there is no one to report a failed signal to, the consumer still finds the data on its next pop
+            let _ = notifier.notify();
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = was_idle;
+    }
+
+    /// Reserves room for a `len` byte message that is written in place through the returned grant.
+    /// Nothing is visible to the consumer until the grant is committed, dropping it abandons the message.
+    pub fn reserve(&mut self, len: usize) -> Result<WriteGrant<'_, 'a>, RingError> {
+        loop {
+            let claimed = if self.contiguous {self.claim_contiguous(len)} else {self.claim(len)};
+            match claimed {
+                Ok((tail, record)) => return Ok(WriteGrant { ring: self, tail, len, record }),
+                Err(RingError::Full) if self.lossy && self.drop_oldest() => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Where the next message goes, which is past the messages of a batch that is being written
+    fn next_tail(&self) -> u64 {
+        self.unpublished.unwrap_or_else(|| self.tail.load(Ordering::Relaxed))
+    }
+
+    /// The free bytes after `tail`. The consumer's cache line is only read when the copy of the head
+    /// from last time does not leave `needed` bytes, the head only ever moves towards the tail,
+    /// so the copy can only make the ring look fuller than it is.
+    fn free_space(&mut self, tail: u64, needed: usize) -> usize {
+        if let Some(head) = self.cached_head {
+            let free = self.size.saturating_sub(calc_curr_bytes(head, tail));
+            if free >= needed {return free;}
+        }
+        self.size.saturating_sub(calc_curr_bytes(self.refresh_head(), tail))
+    }
+
+    /// Where `pos` falls in the buffer
+    pub(crate) fn offset(&self, pos: u64) -> usize {
+        offset(pos, self.size)
+    }
+
+    /// The `len` bytes at `offset`, in two parts if they wrap the end of a buffer that is not mirrored
+    pub(crate) fn parts_mut(&mut self, len: usize, offset: usize) -> PartsMut<'_> {
+        if self.is_mirrored() {
+            (&mut self.buffer[offset..offset + len], None)
         } else {
-            self.buffer[tail..tail+SZ_OF_USIZE].copy_from_slice_avx(&msg_len_bytes);
-            self.buffer[tail+SZ_OF_USIZE..tail+SZ_OF_USIZE+msg_len].copy_from_slice_avx(msg);
-            *self.tail = (tail+SZ_OF_USIZE+msg_len) % self.buffer.len();
+            get_parts_mut(len, offset, self.buffer)
         }
+    }
 
-        msg_len + SZ_OF_USIZE
+    /// Reads the consumer's head and keeps a copy of it
+    pub(crate) fn refresh_head(&mut self) -> u64 {
+        let head = self.head.load(Ordering::Acquire);
+        self.cached_head = Some(head);
+        head
     }
 
-    #[cfg(not(feature = "avx2"))]
-    pub fn push(&mut self, msg: &[u8]) -> usize {
-        //is buffer full?
+    /// Finds room for a `len` byte message, returns where its record starts and how long it is
+    fn claim(&mut self, len: usize) -> Result<(u64, usize), RingError> {
+        let width = self.len_width.bytes();
         //is there room for the message
+        let max = self.size.saturating_sub(width).min(self.len_width.max_len());
 
-        let msg_len = msg.len();
+        if len > max {return Err(RingError::MessageTooLarge { len, max });}
+        //is buffer full?
+        let tail = self.next_tail();
+        if len + width > self.free_space(tail, len + width) {return Err(RingError::Full);}
 
-        if self.is_full() || msg_len + SZ_OF_USIZE > self.empty_slots_left() {return 0;}
+        Ok((tail, len + width))
+    }
 
-        let tail = *self.tail;
-        let msg_len_bytes = msg_len.to_le_bytes();
-        let msg_len_bytes_len = msg_len_bytes.len();
-        let bytes_until_end = self.buffer.len() - tail;
+    fn claim_contiguous(&mut self, len: usize) -> Result<(u64, usize), RingError> {
+        let width = self.len_width.bytes();
+        let record = record_len(len, self.len_width, true);
+        //the largest aligned record that fits in the buffer
+        let max_record = self.size / width * width;
 
-        if bytes_until_end < SZ_OF_USIZE {
-//EXTRA SAD CASE
-            self.buffer[tail..].copy_from_slice(&msg_len_bytes[..bytes_until_end]);
-            self.buffer[..(msg_len_bytes_len-bytes_until_end)].copy_from_slice(&msg_len_bytes[bytes_until_end..]);
-        
-            self.buffer[(msg_len_bytes_len-bytes_until_end)..(msg_len_bytes_len-bytes_until_end)+msg_len].copy_from_slice(msg);
-            
-            *self.tail = msg_len_bytes_len -bytes_until_end + msg_len;
-//SAD CASE
-        } else if bytes_until_end <= SZ_OF_USIZE + msg_len {
-            self.buffer[tail..tail+SZ_OF_USIZE].copy_from_slice(&msg_len_bytes);
-
-            self.buffer[tail+SZ_OF_USIZE..].copy_from_slice(&msg[..(bytes_until_end-SZ_OF_USIZE)]);
-            self.buffer[..msg_len+SZ_OF_USIZE-bytes_until_end].copy_from_slice(&msg[(bytes_until_end-SZ_OF_USIZE)..]);
-
-            *self.tail = msg_len + SZ_OF_USIZE - bytes_until_end;
-//HAPPY CASE
+        if record > max_record || len > self.len_width.max_len() {
+            let max = max_record.saturating_sub(width).min(self.len_width.max_len());
+            return Err(RingError::MessageTooLarge { len, max });
+        }
+
+        let tail = self.next_tail();
+        let tail_offset = self.offset(tail);
+        let bytes_until_end = self.size - tail_offset;
+        let needed = if record <= bytes_until_end {record} else {bytes_until_end + record};
+        let free_space = self.free_space(tail, needed);
+
+        if record <= bytes_until_end {
+            if record > free_space {return Err(RingError::Full);}
+            return Ok((tail, record));
+        }
+
+        //the message has to start over at the beginning, skipping the rest of the buffer
+        if bytes_until_end > free_space {return Err(RingError::Full);}
+        if bytes_until_end >= width {
+            self.buffer[tail_offset..tail_offset+width].copy_from_slice(&self.len_width.encode(PAD_MARKER)[..width]);
+        }
+        let start = tail + bytes_until_end as u64;
+        if bytes_until_end + record > free_space {
+            //publish just the padding, once the reader skips it the whole buffer is available again.
+            //a batch's messages are whole already, so they go out with it
+            self.publish_tail(start);
+            return Err(RingError::Full);
+        }
+        Ok((start, record))
+    }
+
+    /// Moves the head past the oldest message, or past padding in contiguous mode.
+    /// Returns false if the length at the head could not be read.
+    fn drop_oldest(&mut self) -> bool {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        if head == tail {
+            //a batch filled the ring with messages that are not published yet, they can only be dropped once they are
+            if let Some(unpublished) = self.unpublished {
+                self.publish_tail(unpublished);
+            }
+            //otherwise the consumer emptied the ring since we found it full, there is room now
+            return true;
+        }
+
+        let head_offset = self.offset(head);
+        let bytes_until_end = self.size - head_offset;
+        let is_padding = self.contiguous && (bytes_until_end < self.len_width.bytes()
+            || self.len_width.decode(&self.buffer[head_offset..]) == PAD_MARKER);
+        let new_head = if is_padding {
+            head + bytes_until_end as u64
         } else {
-            self.buffer[tail..tail+SZ_OF_USIZE].copy_from_slice(&msg_len_bytes);
-            self.buffer[tail+SZ_OF_USIZE..tail+SZ_OF_USIZE+msg_len].copy_from_slice(msg);
-            *self.tail = (tail+SZ_OF_USIZE+msg_len) % self.buffer.len();
+            //we wrote everything between head and tail ourselves, so the length can be trusted
+            let Ok(msg_len) = peek(head, tail, self.len_width, &self.buffer[..self.size]) else { return false };
+            head + record_len(msg_len, self.len_width, self.contiguous) as u64
+        };
+
+        //if the consumer got there first it freed the space for us, either way there is more room now
+        if self.head.compare_exchange(head, new_head, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            self.cached_head = Some(new_head);
+            if !is_padding {self.dropped += 1;}
         }
+        //keeps the writes over the dropped message from being seen before the new head,
+        //so a consumer that reads them also sees the head move, see RingbufRo::was_dropped
+        fence(Ordering::Release);
+        true
+    }
+}
+
+/// Room for one message in a [`RingbufRw`], in two parts if it wraps the end of the buffer.
+/// The length field and the tail are only written when the grant is committed.
+#[derive(Debug)]
+pub struct WriteGrant<'r, 'a> {
+    ring : &'r mut RingbufRw<'a>,
+    tail : u64,
+    len : usize,
+    record : usize,
+}
+
+impl<'r, 'a> WriteGrant<'r, 'a> {
+    /// The payload to fill in, the second part is only there if the message wraps the end of the buffer
+    pub fn parts_mut(&mut self) -> PartsMut<'_> {
+        let payload = self.ring.offset(self.tail + self.ring.len_width.bytes() as u64);
+        self.ring.parts_mut(self.len, payload)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-        msg_len + SZ_OF_USIZE
+    /// The number of bytes the message takes up in the ring, including its length field
+    pub fn record_len(&self) -> usize {
+        self.record
+    }
+
+    /// The payload as a single slice, which is always the case for a contiguous or a mirrored ring
+    pub fn as_mut_slice(&mut self) -> Option<&mut [u8]> {
+        match self.parts_mut() {
+            (first_part, None) => Some(first_part),
+            _ => None,
+        }
+    }
+
+    /// Fills the whole payload from `msg`, which must be exactly [`WriteGrant::len`] bytes long
+    pub fn copy_from_slice(&mut self, msg: &[u8]) {
+        assert_eq!(self.len, msg.len(), "msg does not match the reserved length");
+        let (first_part, second_part) = self.parts_mut();
+        let first_part_len = first_part.len();
+        copy_bytes(first_part, &msg[..first_part_len]);
+        if let Some(second_part) = second_part {
+            copy_bytes(second_part, &msg[first_part_len..]);
+        }
+    }
+
+    /// Writes the length field and publishes the message to the consumer
+    pub fn commit(mut self) {
+        let tail = self.write_len();
+        self.ring.publish_tail(tail);
+    }
+
+    /// Writes the length field and returns where the tail goes, leaving it to the caller to publish it
+    fn write_len(&mut self) -> u64 {
+        let len_width = self.ring.len_width;
+        let field = &len_width.encode(self.len)[..len_width.bytes()];
+        let offset = self.ring.offset(self.tail);
+        if self.ring.is_mirrored() {
+            self.ring.buffer[offset..offset + field.len()].copy_from_slice(field);
+        } else {
+            copy_in_parts(field, None, offset, self.ring.buffer);
+        }
+        let tail = self.tail + self.record as u64;
+        self.ring.unpublished = Some(tail);
+        tail
     }
 }
 
 impl<'a> Display for RingbufRw<'a> {
     fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error>{
-        let hex: String = self.buffer.iter().map(|&byte| format!("{: >5x}", byte)).collect::<Vec<String>>().join("|");
-        let headtail: String = self.buffer.iter().enumerate()
+        let hex: String = self.buffer[..self.size].iter().map(|&byte| format!("{: >5x}", byte)).collect::<Vec<String>>().join("|");
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        let (head_offset, tail_offset) = (self.offset(head), self.offset(tail));
+        let headtail: String = self.buffer[..self.size].iter().enumerate()
         .map(|(i, &_byte)| {
             if self.is_empty() {
                 String::from("EMPTY")
             } else if self.is_full() {
                 String::from("FULLL")
-            } else if i == *self.head {
+            } else if i == head_offset {
                 String::from("HEAD^")
-            } else if i == *self.tail {
+            } else if i == tail_offset {
                 String::from("TAIL^")
             } else {
                 String::from("     ")
@@ -169,9 +479,9 @@ impl<'a> Display for RingbufRw<'a> {
         .collect::<Vec<String>>().join("|");
         
         write!(format, "\nRing Buffer: tail: {}, head: {}, size: {}\n [ {} ]\n [ {} ]\n",
-                    self.tail,
-                    self.head,
-                    self.buffer.len(),
+                    tail,
+                    head,
+                    self.size,
                     &hex,
                     &headtail)
     }
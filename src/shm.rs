@@ -0,0 +1,388 @@
+use std::{ffi::CString, io, mem::size_of, ptr, os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd}};
+use crate::{
+    CONTROL_SIZE,
+    LenWidth,
+    codec::Pod,
+    error::ShmError,
+    header::{FLAG_CONTIGUOUS, FLAG_HUGE_PAGES, FLAG_LOSSY, FLAG_MIRRORED, HEADER_SIZE, KNOWN_FLAGS, SegmentHeader},
+    ringbuffer_ro::RingbufRo,
+    ringbuffer_rw::RingbufRw,
+    slot_ring::SlotRing,
+};
+
+/// An owned, mapped shared memory segment that holds one ring buffer.
+///
+/// The segment starts with a [`SegmentHeader`], which is written on create and checked on attach.
+/// Every handle unmaps its own view when dropped. Only the handle that created a
+/// named segment unlinks the name, so the peer can keep attaching until then.
+///
+/// A segment created with [`FLAG_MIRRORED`] has its buffer mapped twice in a row by every handle,
+/// so the halves never have to split a message that wraps the end of the buffer in two.
+///
+/// A segment created with [`FLAG_HUGE_PAGES`] tries to get huge pages and falls back to normal ones
+/// if the system has none to spare, [`ShmRing::page_size`] tells which it got.
+#[derive(Debug)]
+pub struct ShmRing {
+    fd : OwnedFd,
+    data : *mut u8,
+    size : usize,
+    capacity : usize,
+    //the size of the pages the kernel backs the segment with
+    page_size : usize,
+    name : Option<CString>,
+}
+
+// The mapping is owned by this handle and the halves it hands out borrow it mutably
+unsafe impl Send for ShmRing {}
+
+impl ShmRing {
+    /// Creates a new named segment (via `shm_open`) with room for `capacity` bytes of messages.
+    /// Fails if a segment with that name already exists. Named segments live on tmpfs, so
+    /// [`FLAG_HUGE_PAGES`] can only ask for transparent huge pages for them.
+    pub fn create(name : &str, capacity : usize) -> Result<Self, ShmError> {
+        Self::create_with_flags(name, capacity, 0)
+    }
+
+    /// Like [`ShmRing::create`], with feature flags from [`crate::header`] recorded in the header
+    pub fn create_with_flags(name : &str, capacity : usize, flags : u64) -> Result<Self, ShmError> {
+        Self::create_named(name, capacity, flags, LenWidth::default(), 0)
+    }
+
+    /// Like [`ShmRing::create_with_flags`], framing messages with a `len_width` length field
+    pub fn create_with_len_width(name : &str, capacity : usize, flags : u64, len_width : LenWidth) -> Result<Self, ShmError> {
+        Self::create_named(name, capacity, flags, len_width, 0)
+    }
+
+    /// Creates a new named segment for a [`SlotRing`] of `slots` values of type `T`, see [`ShmRing::slot_ring`]
+    pub fn create_slots<T: Pod>(name : &str, slots : usize) -> Result<Self, ShmError> {
+        Self::create_named(name, slot_capacity::<T>(slots)?, 0, LenWidth::default(), size_of::<T>())
+    }
+
+    fn create_named(name : &str, capacity : usize, flags : u64, len_width : LenWidth, slot_size : usize) -> Result<Self, ShmError> {
+        let (buffer_offset, size) = segment_layout(capacity, flags)?;
+        let name = to_cstring(name)?;
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o600) };
+        if fd < 0 {return Err(io::Error::last_os_error().into());}
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        match Self::map_new(fd, size, buffer_offset, capacity, flags, len_width, slot_size) {
+            Ok(mut ring) => {
+                ring.name = Some(name);
+                Ok(ring)
+            }
+            Err(e) => {
+                unsafe { libc::shm_unlink(name.as_ptr()) };
+                Err(e)
+            }
+        }
+    }
+
+    /// Attaches to a segment that was created by another handle with [`ShmRing::create`]
+    pub fn open(name : &str) -> Result<Self, ShmError> {
+        let name = to_cstring(name)?;
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR, 0) };
+        if fd < 0 {return Err(io::Error::last_os_error().into());}
+        Self::from_fd(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Creates an unnamed segment backed by `memfd_create`. Share it with the peer by
+    /// passing [`AsRawFd::as_raw_fd`] over a unix socket or through `fork`. With [`FLAG_HUGE_PAGES`]
+    /// the segment comes from hugetlbfs if the system has huge pages reserved, except for mirrored
+    /// segments, which have to be mapped at base page boundaries.
+    #[cfg(target_os = "linux")]
+    pub fn create_anonymous(capacity : usize) -> Result<Self, ShmError> {
+        Self::create_anonymous_with_flags(capacity, 0)
+    }
+
+    /// Like [`ShmRing::create_anonymous`], with feature flags from [`crate::header`] recorded in the header
+    #[cfg(target_os = "linux")]
+    pub fn create_anonymous_with_flags(capacity : usize, flags : u64) -> Result<Self, ShmError> {
+        Self::create_memfd(capacity, flags, LenWidth::default(), 0)
+    }
+
+    /// Like [`ShmRing::create_anonymous_with_flags`], framing messages with a `len_width` length field
+    #[cfg(target_os = "linux")]
+    pub fn create_anonymous_with_len_width(capacity : usize, flags : u64, len_width : LenWidth) -> Result<Self, ShmError> {
+        Self::create_memfd(capacity, flags, len_width, 0)
+    }
+
+    /// Creates an unnamed segment for a [`SlotRing`] of `slots` values of type `T`, see [`ShmRing::slot_ring`]
+    #[cfg(target_os = "linux")]
+    pub fn create_anonymous_slots<T: Pod>(slots : usize) -> Result<Self, ShmError> {
+        Self::create_memfd(slot_capacity::<T>(slots)?, 0, LenWidth::default(), size_of::<T>())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create_memfd(capacity : usize, flags : u64, len_width : LenWidth, slot_size : usize) -> Result<Self, ShmError> {
+        let (buffer_offset, size) = segment_layout(capacity, flags)?;
+        if flags & FLAG_HUGE_PAGES != 0 && flags & FLAG_MIRRORED == 0 {
+            //any error on the way to a hugetlbfs segment falls back to normal pages
+            let huge = memfd(libc::MFD_CLOEXEC | libc::MFD_HUGETLB).and_then(|fd| Ok((page_size_of(&fd)?, fd)));
+            if let Ok((huge_page_size, fd)) = huge {
+                //hugetlbfs only maps whole huge pages, and fails the mapping when none are reserved
+                let size = size.next_multiple_of(huge_page_size);
+                match Self::map_new(fd, size, buffer_offset, capacity, flags, len_width, slot_size) {
+                    Err(ShmError::Io(_)) => {}
+                    ring => return ring,
+                }
+            }
+        }
+        let fd = memfd(libc::MFD_CLOEXEC)?;
+        Self::map_new(fd, size, buffer_offset, capacity, flags, len_width, slot_size)
+    }
+
+    /// Attaches to an existing segment, e.g. a memfd received from the peer.
+    /// The segment's header is checked before any of it is handed out as a ring.
+    pub fn from_fd(fd : OwnedFd) -> Result<Self, ShmError> {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } < 0 {return Err(io::Error::last_os_error().into());}
+        let size = stat.st_size as usize;
+        if size < HEADER_SIZE {
+            return Err(ShmError::CapacityMismatch { header: 0, segment: size as u64 });
+        }
+
+        let mut ring = Self::map(fd, size)?;
+        let header = unsafe { SegmentHeader::attach(ring.data, size) }?;
+        let (capacity, flags, buffer_offset) = (header.capacity(), header.flags(), header.buffer_offset());
+        ring.capacity = capacity;
+        if flags & FLAG_MIRRORED != 0 {ring.mirror(buffer_offset, capacity)?;}
+        if flags & FLAG_HUGE_PAGES != 0 {ring.advise_huge_pages();}
+        Ok(ring)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn map_new(fd : OwnedFd, size : usize, buffer_offset : usize, capacity : usize, flags : u64, len_width : LenWidth, slot_size : usize) -> Result<Self, ShmError> {
+        if flags & !KNOWN_FLAGS != 0 {return Err(ShmError::UnsupportedFlags { flags: flags & !KNOWN_FLAGS });}
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), size as libc::off_t) } < 0 {return Err(io::Error::last_os_error().into());}
+        let mut ring = Self::map(fd, size)?;
+        unsafe { SegmentHeader::init_with(ring.data, capacity, flags, len_width, slot_size, buffer_offset) };
+        ring.capacity = capacity;
+        if flags & FLAG_MIRRORED != 0 {ring.mirror(buffer_offset, capacity)?;}
+        if flags & FLAG_HUGE_PAGES != 0 {ring.advise_huge_pages();}
+        Ok(ring)
+    }
+
+    fn map(fd : OwnedFd, size : usize) -> Result<Self, ShmError> {
+        let data = unsafe {
+            libc::mmap(ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd.as_raw_fd(), 0)
+        };
+        if data == libc::MAP_FAILED {return Err(io::Error::last_os_error().into());}
+        let mut ring = Self { fd, data: data as *mut u8, size, capacity: 0, page_size: 0, name: None };
+        ring.page_size = page_size_of(&ring.fd)?;
+        Ok(ring)
+    }
+
+    /// Asks the kernel to back the mapping with transparent huge pages. It is only a hint, so a kernel
+    /// without them or with them turned off for shared memory is not an error.
+    fn advise_huge_pages(&self) {
+        #[cfg(target_os = "linux")]
+        unsafe { libc::madvise(self.data as *mut libc::c_void, self.size, libc::MADV_HUGEPAGE) };
+    }
+
+    /// Replaces the mapping with one where the `capacity` byte buffer at `buffer_offset` is followed by
+    /// a second mapping of the same pages. Both must be multiples of the page size.
+    fn mirror(&mut self, buffer_offset : usize, capacity : usize) -> io::Result<()> {
+        //reserve room for the whole segment and the mirror first, so both land right next to each other
+        let size = buffer_offset + 2 * capacity;
+        let base = unsafe {
+            libc::mmap(ptr::null_mut(), size, libc::PROT_NONE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)
+        };
+        if base == libc::MAP_FAILED {return Err(io::Error::last_os_error());}
+
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_SHARED | libc::MAP_FIXED;
+        let fd = self.fd.as_raw_fd();
+        let segment = unsafe { libc::mmap(base, buffer_offset + capacity, prot, flags, fd, 0) };
+        let mirror = if segment == libc::MAP_FAILED {segment} else {
+            unsafe { libc::mmap(base.add(buffer_offset + capacity), capacity, prot, flags, fd, buffer_offset as libc::off_t) }
+        };
+        if mirror == libc::MAP_FAILED {
+            let e = io::Error::last_os_error();
+            unsafe { libc::munmap(base, size) };
+            return Err(e);
+        }
+
+        unsafe { libc::munmap(self.data as *mut libc::c_void, self.size) };
+        self.data = base as *mut u8;
+        self.size = size;
+        Ok(())
+    }
+
+    /// Rounds `capacity` up to the next power of two, so the ring finds its indexes with a mask
+    /// instead of a division. Returns `None` if there is no such power of two.
+    pub const fn round_capacity(capacity : usize) -> Option<usize> {
+        capacity.checked_next_power_of_two()
+    }
+
+    /// The size of the pages the segment is backed by, larger than the base page size when it got
+    /// huge pages from hugetlbfs. Transparent huge pages are up to the kernel and do not show up here.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// The number of bytes available to messages, including their length fields
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The header this segment was created with
+    pub fn header(&self) -> &SegmentHeader {
+        unsafe { &*(self.data as *const SegmentHeader) }
+    }
+
+    /// The width of the length fields recorded in the header
+    pub fn len_width(&self) -> LenWidth {
+        //attach already rejected any other width
+        LenWidth::from_bytes(self.header().len_width()).unwrap_or_default()
+    }
+
+    /// Whether the buffer is mapped twice, see [`FLAG_MIRRORED`]
+    pub fn is_mirrored(&self) -> bool {
+        self.header().flags() & FLAG_MIRRORED != 0
+    }
+
+    /// The producer half of the ring, set up for the flags and length width in the header.
+    /// Returns [`ShmError::SlotSizeMismatch`] if the segment holds a [`SlotRing`], see [`ShmRing::slot_ring`] for those.
+    pub fn producer(&mut self) -> Result<RingbufRw<'_>, ShmError> {
+        self.check_byte_ring()?;
+        //the mutable borrow keeps any other half of this mapping from being handed out
+        Ok(unsafe { self.new_producer() })
+    }
+
+    /// The consumer half of the ring, set up for the flags and length width in the header.
+    /// Returns [`ShmError::SlotSizeMismatch`] if the segment holds a [`SlotRing`], see [`ShmRing::slot_ring`] for those.
+    pub fn consumer(&mut self) -> Result<RingbufRo<'_>, ShmError> {
+        self.check_byte_ring()?;
+        Ok(unsafe { self.new_consumer() })
+    }
+
+    /// Both halves of the ring, for when one process both produces and consumes.
+    /// Returns [`ShmError::SlotSizeMismatch`] if the segment holds a [`SlotRing`], see [`ShmRing::slot_ring`] for those.
+    ///
+    /// # Safety
+    ///
+    /// The producer holds the buffer as `&mut [u8]` while the consumer holds it as `&[u8]`, which Rust
+    /// does not allow for the same bytes. This is the same contract as creating both halves with
+    /// [`RingbufRw::new`] and [`RingbufRo::new`] over one mapping: the caller accepts that aliasing and
+    /// only touches the buffer through the halves, which each stay on the bytes the head and tail give them.
+    pub unsafe fn split(&mut self) -> Result<(RingbufRw<'_>, RingbufRo<'_>), ShmError> {
+        self.check_byte_ring()?;
+        Ok(unsafe { (self.new_producer(), self.new_consumer()) })
+    }
+
+    /// # Safety
+    ///
+    /// Hands out the buffer as `&mut [u8]` through a shared borrow, so the caller has to hold `self`
+    /// mutably borrowed for as long as the producer lives, see [`ShmRing::split`] for the exception.
+    unsafe fn new_producer(&self) -> RingbufRw<'_> {
+        let flags = self.header().flags();
+        let size = CONTROL_SIZE + self.capacity;
+        let mut writer = unsafe {
+            if self.is_mirrored() {RingbufRw::new_mirrored(size, self.control())} else {RingbufRw::new(size, self.control())}
+        };
+        writer.set_len_width(self.len_width());
+        writer.set_contiguous(flags & FLAG_CONTIGUOUS != 0);
+        writer.set_lossy(flags & FLAG_LOSSY != 0);
+        writer
+    }
+
+    /// # Safety
+    ///
+    /// Like [`ShmRing::new_producer`], the caller has to hold `self` mutably borrowed while the consumer lives
+    unsafe fn new_consumer(&self) -> RingbufRo<'_> {
+        let flags = self.header().flags();
+        let size = CONTROL_SIZE + self.capacity;
+        let mut reader = unsafe {
+            if self.is_mirrored() {RingbufRo::new_mirrored(size, self.control())} else {RingbufRo::new(size, self.control())}
+        };
+        reader.set_len_width(self.len_width());
+        reader.set_contiguous(flags & FLAG_CONTIGUOUS != 0);
+        reader.set_lossy(flags & FLAG_LOSSY != 0);
+        reader
+    }
+
+    //length prefixed messages would be framed over the slots
+    fn check_byte_ring(&self) -> Result<(), ShmError> {
+        let slot_size = self.header().slot_size();
+        if slot_size != 0 {
+            return Err(ShmError::SlotSizeMismatch { expected: 0, found: slot_size });
+        }
+        Ok(())
+    }
+
+    /// The control block the halves of the ring start at, right in front of the buffer
+    fn control(&self) -> *mut u8 {
+        //attach already checked the buffer starts after the header and the control block
+        unsafe { self.data.add(self.header().buffer_offset() - CONTROL_SIZE) }
+    }
+
+    /// A handle to the [`SlotRing`] in a segment made with [`ShmRing::create_slots`]. Each side of the
+    /// ring takes its own handle, checked against the slot size recorded in the header.
+    pub fn slot_ring<T: Pod>(&mut self) -> Result<SlotRing<'_, T>, ShmError> {
+        let slot_size = self.header().slot_size();
+        if slot_size != size_of::<T>() {
+            return Err(ShmError::SlotSizeMismatch { expected: size_of::<T>(), found: slot_size });
+        }
+        Ok(unsafe { SlotRing::new(CONTROL_SIZE + self.capacity, self.control()) })
+    }
+}
+
+impl AsRawFd for ShmRing {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.data as *mut libc::c_void, self.size) };
+        if let Some(name) = &self.name {
+            unsafe { libc::shm_unlink(name.as_ptr()) };
+        }
+    }
+}
+
+/// Where the buffer of a new segment starts and how big the segment is. A mirrored segment
+/// starts its buffer on a page of its own and needs a whole number of pages for it.
+fn segment_layout(capacity : usize, flags : u64) -> io::Result<(usize, usize)> {
+    if capacity < 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "capacity must be at least 2 bytes"));
+    }
+    if flags & FLAG_MIRRORED == 0 {return Ok((HEADER_SIZE + CONTROL_SIZE, HEADER_SIZE + CONTROL_SIZE + capacity));}
+
+    let page_size = base_page_size();
+    if !capacity.is_multiple_of(page_size) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "a mirrored ring's capacity must be a multiple of the page size"));
+    }
+    let buffer_offset = (HEADER_SIZE + CONTROL_SIZE).next_multiple_of(page_size);
+    Ok((buffer_offset, buffer_offset + capacity))
+}
+
+fn base_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// The size of the pages backing `fd`, hugetlbfs reports its huge page size as the block size
+fn page_size_of(fd : &OwnedFd) -> io::Result<usize> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } < 0 {return Err(io::Error::last_os_error());}
+    Ok((stat.st_blksize as usize).max(base_page_size()))
+}
+
+#[cfg(target_os = "linux")]
+fn memfd(flags : libc::c_uint) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::memfd_create(c"shm_ring".as_ptr(), flags) };
+    if fd < 0 {return Err(io::Error::last_os_error());}
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn slot_capacity<T: Pod>(slots : usize) -> io::Result<usize> {
+    if slots < 2 || size_of::<T>() == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "a slot ring needs at least 2 slots of a sized type"));
+    }
+    slots.checked_mul(size_of::<T>()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "slot ring is too large"))
+}
+
+fn to_cstring(name : &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name cannot contain a nul byte"))
+}
@@ -0,0 +1,156 @@
+use std::{fmt::{Display, Formatter}, marker::PhantomData, mem::{align_of, size_of}, ptr, sync::atomic::{AtomicU64, Ordering}};
+use crate::{CACHE_LINE, CONTROL_SIZE, codec::Pod, drain_and_fill::offset, error::RingError, futex::Waiters};
+
+/// A ring of fixed size [`Pod`] values. It has the same control block as the byte ring, but
+/// stores each value in its own aligned slot with no length in front of it, and `tail` and `head`
+/// count slots instead of bytes. Every slot can be filled, since the positions only ever grow.
+///
+/// Like the byte ring it is single producer, single consumer: each side makes its own handle over
+/// the same memory, only the producer's handle may push and only the consumer's may pop.
+#[derive(Debug)]
+pub struct SlotRing<'a, T: Pod> {
+    head : &'a AtomicU64,
+    tail : &'a AtomicU64,
+    //not used yet, keeps the layout the same as the byte ring
+    _waiters : &'a Waiters,
+    slots : *mut T,
+    capacity : usize,
+    //the last positions read from the other side's cache line
+    cached_head : u64,
+    cached_tail : u64,
+    _buffer : PhantomData<&'a mut [T]>,
+}
+
+// The producer only writes to free slots and the consumer only reads full ones
+unsafe impl<T: Pod> Send for SlotRing<'_, T> {}
+
+impl<'a, T: Pod> SlotRing<'a, T> {
+    /// # Safety
+    ///
+    /// `data` must point to `size` bytes, [`CONTROL_SIZE`] of them for the control block followed by
+    /// the slots, which must be aligned for `T`. Both handles must be created with the same `size`.
+    pub unsafe fn new(size : usize, data : *mut u8) -> Self {
+        if data.is_null() {panic!("data cannot be null")}
+        assert!(size_of::<T>() != 0, "slots cannot be zero sized");
+        let tail : &AtomicU64 = unsafe { &*(data as *const AtomicU64) };
+        let head : &AtomicU64 = unsafe { &*(data.add(CACHE_LINE) as *const AtomicU64) };
+        let waiters : &Waiters = unsafe { &*(data.add(2 * CACHE_LINE) as *const Waiters) };
+        let slots = unsafe { data.add(CONTROL_SIZE) };
+        assert_eq!(0, slots as usize % align_of::<T>(), "slots must be aligned for the type");
+        let capacity = size.saturating_sub(CONTROL_SIZE) / size_of::<T>();
+        assert!(capacity >= 2, "a slot ring needs room for at least 2 slots");
+        let (cached_head, cached_tail) = (head.load(Ordering::Acquire), tail.load(Ordering::Acquire));
+        Self { head, tail, _waiters: waiters, slots: slots as *mut T, capacity, cached_head, cached_tail, _buffer: PhantomData }
+    }
+
+    /// The number of slots
+    pub fn get_size(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of values ever popped
+    pub fn get_head(&self) -> u64 {
+        self.head.load(Ordering::Relaxed)
+    }
+
+    /// The number of values ever pushed
+    pub fn get_tail(&self) -> u64 {
+        self.tail.load(Ordering::Relaxed)
+    }
+
+    /// The number of values waiting to be popped
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.saturating_sub(head) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    /// The slot `pos` falls in
+    fn slot(&self, pos : u64) -> *mut T {
+        unsafe { self.slots.add(offset(pos, self.capacity)) }
+    }
+
+    /// Pushes `value`, or returns [`RingError::Full`] if every slot is taken
+    pub fn push(&mut self, value : T) -> Result<(), RingError> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail - self.cached_head == self.capacity as u64 {
+            self.cached_head = self.head.load(Ordering::Acquire);
+            if tail - self.cached_head == self.capacity as u64 {return Err(RingError::Full);}
+        }
+        unsafe { self.slot(tail).write(value) };
+        self.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Pops the oldest value, or returns `None` if the ring is empty
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.cached_tail {
+            self.cached_tail = self.tail.load(Ordering::Acquire);
+            if head == self.cached_tail {return None;}
+        }
+        let value = unsafe { self.slot(head).read() };
+        self.head.store(head + 1, Ordering::Release);
+        Some(value)
+    }
+
+    /// Pushes as many of `values` as there are free slots for and returns how many that was.
+    /// The tail is only published once, after all of them are written.
+    pub fn push_slice(&mut self, values : &[T]) -> usize {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let mut free = self.capacity - (tail - self.cached_head) as usize;
+        if free < values.len() {
+            self.cached_head = self.head.load(Ordering::Acquire);
+            free = self.capacity - (tail - self.cached_head) as usize;
+        }
+        let count = values.len().min(free);
+        if count == 0 {return 0;}
+
+        let first = count.min(self.capacity - offset(tail, self.capacity));
+        unsafe {
+            ptr::copy_nonoverlapping(values.as_ptr(), self.slot(tail), first);
+            ptr::copy_nonoverlapping(values.as_ptr().add(first), self.slots, count - first);
+        }
+        self.tail.store(tail + count as u64, Ordering::Release);
+        count
+    }
+
+    /// Pops up to `values.len()` values into `values` and returns how many that was.
+    /// The head is only published once, after all of them are read.
+    pub fn pop_slice(&mut self, values : &mut [T]) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        let mut available = (self.cached_tail - head) as usize;
+        if available < values.len() {
+            self.cached_tail = self.tail.load(Ordering::Acquire);
+            available = (self.cached_tail - head) as usize;
+        }
+        let count = values.len().min(available);
+        if count == 0 {return 0;}
+
+        let first = count.min(self.capacity - offset(head, self.capacity));
+        unsafe {
+            ptr::copy_nonoverlapping(self.slot(head), values.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(self.slots, values.as_mut_ptr().add(first), count - first);
+        }
+        self.head.store(head + count as u64, Ordering::Release);
+        count
+    }
+}
+
+impl<T: Pod> Display for SlotRing<'_, T> {
+    fn fmt(&self, format : &mut Formatter) -> Result<(), std::fmt::Error>{
+        write!(format, "\nSlot Ring Buffer: head: {}, tail: {}, slots: {}, slot size: {}\n",
+                self.get_head(),
+                self.get_tail(),
+                self.capacity,
+                size_of::<T>())
+    }
+}
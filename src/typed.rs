@@ -0,0 +1,77 @@
+use std::marker::PhantomData;
+use crate::{codec::Codec, error::TypedError, ringbuffer_ro::RingbufRo, ringbuffer_rw::RingbufRw};
+
+/// Sends values of type `T` through a [`RingbufRw`], one message per value, encoded with `C`
+#[derive(Debug)]
+pub struct TypedRingWriter<'a, T, C> {
+    ring : RingbufRw<'a>,
+    scratch : Vec<u8>,
+    _codec : PhantomData<fn(&T) -> C>,
+}
+
+impl<'a, T, C: Codec<T>> TypedRingWriter<'a, T, C> {
+    pub fn new(ring : RingbufRw<'a>) -> Self {
+        Self { ring, scratch: Vec::new(), _codec: PhantomData }
+    }
+
+    /// Encodes `value` and pushes it, returning the number of bytes it took up in the ring
+    pub fn try_send(&mut self, value : &T) -> Result<usize, TypedError<C::Error>> {
+        let bytes = C::encode(value, &mut self.scratch).map_err(TypedError::Codec)?;
+        Ok(self.ring.try_push(bytes)?)
+    }
+
+    pub fn get_ring(&self) -> &RingbufRw<'a> {
+        &self.ring
+    }
+
+    pub fn into_inner(self) -> RingbufRw<'a> {
+        self.ring
+    }
+}
+
+/// Receives values of type `T` from a [`RingbufRo`] that were sent by a [`TypedRingWriter`] with the same `C`
+#[derive(Debug)]
+pub struct TypedRingReader<'a, T, C> {
+    ring : RingbufRo<'a>,
+    scratch : Vec<u8>,
+    _codec : PhantomData<fn() -> (T, C)>,
+}
+
+impl<'a, T, C: Codec<T>> TypedRingReader<'a, T, C> {
+    pub fn new(ring : RingbufRo<'a>) -> Self {
+        Self { ring, scratch: Vec::new(), _codec: PhantomData }
+    }
+
+    /// Pops the next message and decodes it. A message that fails to decode is still consumed,
+    /// so one bad message does not block the ones behind it.
+    pub fn try_recv(&mut self) -> Result<T, TypedError<C::Error>> {
+        if self.ring.is_lossy() {
+            //a borrowed message could be overwritten, so it is copied out first
+            let msg_len = self.ring.pop_into_vec(&mut self.scratch)?;
+            return C::decode(&self.scratch[..msg_len]).map_err(TypedError::Codec);
+        }
+
+        let msg = self.ring.read()?;
+        //decoded in place, unless the message wraps the end of the buffer
+        let decoded = match msg.as_slice() {
+            Some(bytes) => C::decode(bytes),
+            None => {
+                let (first_part, second_part) = msg.parts();
+                self.scratch.clear();
+                self.scratch.extend_from_slice(first_part);
+                self.scratch.extend_from_slice(second_part.unwrap_or_default());
+                C::decode(&self.scratch)
+            }
+        };
+        msg.commit()?;
+        decoded.map_err(TypedError::Codec)
+    }
+
+    pub fn get_ring(&self) -> &RingbufRo<'a> {
+        &self.ring
+    }
+
+    pub fn into_inner(self) -> RingbufRo<'a> {
+        self.ring
+    }
+}
@@ -0,0 +1,147 @@
+#[cfg(all(test, feature = "async"))]
+mod async_tests{
+    use futures::{SinkExt, StreamExt, executor::block_on};
+    use shm_ring::{
+            CONTROL_SIZE,
+            async_ring::{AsyncRingReader, AsyncRingWriter},
+            error::RingError,
+            ringbuffer_ro::RingbufRo,
+            ringbuffer_rw::RingbufRw,
+    };
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 36;//tail, head and wait words, 36 for buffer
+    const MSGS: u32 = 2_000;
+
+    /// Verifies the stream is woken by a plain producer on another thread
+    #[test]
+    fn stream_from_thread(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let reader = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut writer = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    writer.push_blocking(&i.to_le_bytes(), None).unwrap();
+                }
+            });
+            let mut reader = AsyncRingReader::new(reader).unwrap();
+            block_on(async {
+                for i in 0..MSGS {
+                    assert_eq!(Some(Ok(i.to_le_bytes().to_vec())), reader.next().await);
+                }
+            });
+        });
+    }
+
+    /// Verifies the sink waits for room instead of failing when the ring is full
+    #[test]
+    fn sink_to_thread(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut reader = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let writer = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                let mut dst = [0;4];
+                for i in 0..MSGS {
+                    assert_eq!(Ok(4), reader.pop_blocking(&mut dst, None));
+                    assert_eq!(i.to_le_bytes(), dst);
+                }
+            });
+            let mut writer = AsyncRingWriter::new(writer).unwrap();
+            block_on(async {
+                for i in 0..MSGS {
+                    writer.send(i.to_le_bytes()).await.unwrap();
+                }
+            });
+        });
+    }
+
+    /// Verifies both halves can run as tasks on one executor
+    #[test]
+    fn stream_and_sink(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut reader = AsyncRingReader::new(unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) }).unwrap();
+        let mut writer = AsyncRingWriter::new(unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) }).unwrap();
+
+        block_on(async {
+            let send = async {
+                for i in 0..MSGS {
+                    writer.send(i.to_le_bytes()).await.unwrap();
+                }
+                assert_eq!(Err(RingError::MessageTooLarge { len: 29, max: 28 }), writer.send([0;29]).await);
+            };
+            let receive = async {
+                for i in 0..MSGS {
+                    assert_eq!(Some(Ok(i.to_le_bytes().to_vec())), reader.next().await);
+                }
+            };
+            futures::join!(send, receive);
+        });
+    }
+
+    /// Verifies a lossy stream only yields the messages the producer kept
+    #[test]
+    fn lossy_stream(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut writer = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+        ring.set_lossy(true);
+        writer.set_lossy(true);
+        let mut reader = AsyncRingReader::new(ring).unwrap();
+
+        for i in 0..10u64 {
+            writer.push(&i.to_le_bytes());
+        }
+        assert!(writer.get_dropped() > 0);
+        block_on(async {
+            assert_eq!(Some(Ok(8u64.to_le_bytes().to_vec())), reader.next().await);
+            assert_eq!(Some(Ok(9u64.to_le_bytes().to_vec())), reader.next().await);
+        });
+    }
+
+    /// Stands in for a reactor, blocking in poll until the eventfd is readable
+    #[cfg(target_os = "linux")]
+    struct PollReadiness(shm_ring::notify::Notifier);
+
+    #[cfg(target_os = "linux")]
+    impl shm_ring::async_ring::NotifierReadiness for PollReadiness {
+        fn poll_readable(&mut self, _cx: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+            use std::os::fd::AsRawFd;
+            let mut fd = libc::pollfd { fd: self.0.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            assert_eq!(1, unsafe { libc::poll(&mut fd, 1, 5_000) });
+            self.0.drain().unwrap();
+            std::task::Poll::Ready(())
+        }
+    }
+
+    /// Verifies the stream is woken through the producer's eventfd instead of a thread of its own
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stream_with_notifier(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let reader = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut writer = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+        let notifier = shm_ring::notify::Notifier::new().unwrap();
+        writer.set_notifier(Some(notifier.try_clone().unwrap()));
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    writer.push_blocking(&i.to_le_bytes(), None).unwrap();
+                }
+            });
+            let mut reader = AsyncRingReader::with_notifier(reader, PollReadiness(notifier));
+            block_on(async {
+                for i in 0..MSGS {
+                    assert_eq!(Some(Ok(i.to_le_bytes().to_vec())), reader.next().await);
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod broadcast_tests{
+    use shm_ring::{
+            broadcast::{BroadcastReader, BroadcastWriter, Overflow, broadcast_control_size},
+            error::RingError,
+    };
+    const READERS: usize = 2;
+    const TEST_SHM_SIZE: usize = broadcast_control_size(READERS) + 64;
+
+    /// Verifies every reader sees every message, and readers can leave and join
+    #[test]
+    fn every_reader_gets_every_msg(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer = unsafe{ BroadcastWriter::new(TEST_SHM_SIZE, ptr, READERS, Overflow::BackPressure) };
+        let mut first = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        let mut second = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        assert!(unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.is_none());
+        assert_eq!(2, writer.readers());
+
+        assert_eq!(Ok(16), writer.try_push(b"AAAAB"));
+        assert_eq!(Ok(8), writer.try_push(b""));
+        let mut dst = [0;64];
+        for reader in [&mut first, &mut second] {
+            assert_eq!(Ok(5), reader.try_pop(&mut dst));
+            assert_eq!(b"AAAAB", &dst[..5]);
+            assert_eq!(Ok(0), reader.try_pop(&mut dst));
+            assert_eq!(Err(RingError::Empty), reader.try_pop(&mut dst));
+        }
+
+        drop(second);
+        assert_eq!(1, writer.readers());
+        writer.push(b"CCCC");
+        // a new reader starts at the tail, it does not see older messages
+        let mut third = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        assert!(third.is_empty());
+        assert_eq!(4, first.pop(&mut dst));
+        assert_eq!(0, third.pop(&mut dst));
+    }
+
+    /// Verifies the writer waits for the slowest reader and pads records that do not fit before the end
+    #[test]
+    fn back_pressure(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer = unsafe{ BroadcastWriter::new(TEST_SHM_SIZE, ptr, READERS, Overflow::BackPressure) };
+        let mut fast = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        let mut slow = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        let mut dst = [0;64];
+
+        assert_eq!(Err(RingError::MessageTooLarge { len: 57, max: 56 }), writer.try_push(&[0;57]));
+        assert_eq!(Ok(40), writer.try_push(&[1;32]));
+        assert_eq!(Ok(32), fast.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Full), writer.try_push(&[2;24]));
+
+        assert_eq!(Ok(32), slow.try_pop(&mut dst));
+        // only 24 bytes are left before the end, so they become padding
+        assert_eq!(Ok(32), writer.try_push(&[2;24]));
+        assert_eq!(96, writer.get_tail());
+        for reader in [&mut fast, &mut slow] {
+            assert_eq!(Ok(24), reader.try_pop(&mut dst));
+            assert_eq!([2;24], dst[..24]);
+        }
+    }
+
+    /// Verifies a reader that falls a whole buffer behind finds out and carries on from the newest message
+    #[test]
+    fn overwrite_laps_slow_reader(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer = unsafe{ BroadcastWriter::new(TEST_SHM_SIZE, ptr, READERS, Overflow::Overwrite) };
+        let mut fast = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        let mut slow = unsafe{ BroadcastReader::join(TEST_SHM_SIZE, ptr, READERS) }.unwrap();
+        let mut dst = [0;64];
+
+        for i in 0..10u8 {
+            assert_eq!(Ok(16), writer.try_push(&[i;8]));
+            assert_eq!(Ok(8), fast.try_pop(&mut dst));
+            assert_eq!([i;8], dst[..8]);
+        }
+        assert_eq!(Err(RingError::Lapped { skipped: 160 }), slow.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Empty), slow.try_pop(&mut dst));
+        writer.push(b"AAAA");
+        assert_eq!(Ok(4), slow.try_pop(&mut dst));
+        assert_eq!(b"AAAA", &dst[..4]);
+    }
+
+    /// Verifies readers on other threads see every message in order while the writer waits for them
+    #[test]
+    fn readers_across_threads(){
+        const MSGS: u32 = 5_000;
+        const READERS: usize = 3;
+        let size = broadcast_control_size(READERS) + 256;
+        let mut buffer: Vec<u64> = vec![0;size/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer = unsafe{ BroadcastWriter::new(size, ptr, READERS, Overflow::BackPressure) };
+        let readers: Vec<_> = (0..READERS).map(|_| unsafe{ BroadcastReader::join(size, ptr, READERS) }.unwrap()).collect();
+
+        std::thread::scope(|s| {
+            for mut reader in readers {
+                s.spawn(move || {
+                    let mut dst = [0;8];
+                    for i in 0..MSGS {
+                        let len = 4 + i as usize % 5;
+                        let mut sz = reader.pop(&mut dst);
+                        while sz == 0 {
+                            std::thread::yield_now();
+                            sz = reader.pop(&mut dst);
+                        }
+                        assert_eq!(len, sz);
+                        assert_eq!(i.to_le_bytes(), dst[..4]);
+                    }
+                });
+            }
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    let msg = [i.to_le_bytes(), i.to_le_bytes()].concat();
+                    while writer.push(&msg[..4 + i as usize % 5]) == 0 {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+        });
+    }
+}
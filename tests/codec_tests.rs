@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod codec_tests{
+    use shm_ring::{
+            CONTROL_SIZE,
+            codec::{Pod, PodCodec},
+            error::{RingError, SizeMismatch, TypedError},
+            ringbuffer_ro::RingbufRo,
+            ringbuffer_rw::RingbufRw,
+            typed::{TypedRingReader, TypedRingWriter},
+    };
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 64;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Quote {
+        price: f64,
+        size: u32,
+        venue: [u8; 4],
+    }
+    unsafe impl Pod for Quote {}
+
+    /// Verifies Pod values round trip, including one that wraps the end of the buffer
+    #[test]
+    fn pod_round_trip(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer: TypedRingWriter<Quote, PodCodec<Quote>> = TypedRingWriter::new(unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) });
+        let mut reader: TypedRingReader<Quote, PodCodec<Quote>> = TypedRingReader::new(unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) });
+
+        assert_eq!(Err(TypedError::Ring(RingError::Empty)), reader.try_recv());
+        for i in 0..10 {
+            let quote = Quote { price: i as f64 * 0.5, size: i, venue: *b"XNYS" };
+            assert_eq!(Ok(24), writer.try_send(&quote));
+            assert_eq!(Ok(quote), reader.try_recv());
+        }
+        assert!(reader.get_ring().is_empty());
+    }
+
+    /// Verifies a message of the wrong size is reported and consumed
+    #[test]
+    fn pod_size_mismatch(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut writer = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+        let mut reader: TypedRingReader<u64, PodCodec<u64>> = TypedRingReader::new(unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) });
+
+        writer.push(b"AAAA");
+        writer.push(&7u64.to_ne_bytes());
+        assert_eq!(Err(TypedError::Codec(SizeMismatch { expected: 8, found: 4 })), reader.try_recv());
+        assert_eq!(Ok(7), reader.try_recv());
+    }
+
+    /// Verifies a lossy reader only ever gets whole values or an empty ring while the writer drops old ones
+    #[test]
+    fn pod_lossy_across_threads(){
+        const VALUES: u64 = 20_000;
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+        ring.set_lossy(true);
+        let mut writer: TypedRingWriter<u64, PodCodec<u64>> = TypedRingWriter::new(ring);
+        let mut ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        ring.set_lossy(true);
+        let mut reader: TypedRingReader<u64, PodCodec<u64>> = TypedRingReader::new(ring);
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..VALUES {
+                    assert_eq!(Ok(16), writer.try_send(&i));
+                }
+            });
+            s.spawn(move || {
+                let mut last = None;
+                while last != Some(VALUES - 1) {
+                    match reader.try_recv() {
+                        Ok(i) => {
+                            assert!(last.is_none_or(|last| last < i));
+                            last = Some(i);
+                        }
+                        Err(e) => {
+                            assert_eq!(TypedError::Ring(RingError::Empty), e);
+                            std::thread::yield_now();
+                        }
+                    }
+                }
+            });
+        });
+    }
+
+    #[cfg(any(feature = "bincode", feature = "postcard"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Order {
+        id: u64,
+        symbol: String,
+        legs: Vec<i32>,
+    }
+
+    /// Verifies serde values round trip with each codec
+    #[cfg(any(feature = "bincode", feature = "postcard"))]
+    #[test]
+    fn serde_round_trip(){
+        fn round_trip<C: shm_ring::codec::Codec<Order>>() where C::Error: std::fmt::Debug {
+            let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+            let ptr = buffer.as_mut_ptr() as *mut u8;
+            let mut writer: TypedRingWriter<Order, C> = TypedRingWriter::new(unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) });
+            let mut reader: TypedRingReader<Order, C> = TypedRingReader::new(unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) });
+            for id in 0..10 {
+                let order = Order { id, symbol: "AAPL".into(), legs: vec![1, -2] };
+                writer.try_send(&order).unwrap();
+                assert_eq!(order, reader.try_recv().unwrap());
+            }
+        }
+        #[cfg(feature = "bincode")]
+        round_trip::<shm_ring::codec::BincodeCodec>();
+        #[cfg(feature = "postcard")]
+        round_trip::<shm_ring::codec::PostcardCodec>();
+    }
+}
@@ -1,12 +1,14 @@
 #[cfg(test)]
 mod drain_and_fill_tests{
     use shm_ring::{
-            SZ_OF_USIZE, 
+            CONTROL_SIZE,
+            LenWidth,
             drain_and_fill::drain_and_fill, 
             ringbuffer_ro::RingbufRo, 
-            ringbuffer_rw::RingbufRw
+            ringbuffer_rw::RingbufRw,
+            error::RingError,
     };
-    const TEST_SHM_SIZE: usize = 52;//8 for head, 8 for tail, 36 for buffer (35 that are available)
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 36;//tail, head and wait words, 36 for buffer
 
     /// Verifies that an empty reader doesnt do anything
     #[test]
@@ -42,7 +44,7 @@ mod drain_and_fill_tests{
 
         // set the writer to full
         let size = writer2.get_size();
-        writer2.set_tail(size - 1);
+        writer2.set_tail(size as u64);
         assert!(writer2.is_full());
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
@@ -76,7 +78,7 @@ mod drain_and_fill_tests{
         assert!(writer2.is_empty()); // Verify writer2 is empty
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!((msg.len() + SZ_OF_USIZE)*2, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!((msg.len() + LenWidth::default().bytes())*2, amt); // Verify drain_and_fill wrote the bytes
 
         assert!(!reader1.is_empty()); // Verify reader1 is NOT empty
         assert!(!writer2.is_empty()); // Verify writer2 is NOT empty
@@ -121,7 +123,7 @@ mod drain_and_fill_tests{
         // println!("{reader1}");
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!(msg.len() + SZ_OF_USIZE, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!(msg.len() + LenWidth::default().bytes(), amt); // Verify drain_and_fill wrote the bytes
         // println!("{writer2}");
 
         assert!(reader1.is_empty()); // Verify reader1 is empty
@@ -145,9 +147,9 @@ mod drain_and_fill_tests{
         let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
 
         // Advance the head and tail of the writer to 2 bytes before the end to force a wrap on write
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 2;
-        reader2.set_head(buffer_end);
-        writer2.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 2;
+        reader2.set_head(buffer_end as u64);
+        writer2.set_tail(buffer_end as u64);
 
         assert!(reader2.is_empty());
         assert!(writer2.is_empty());
@@ -159,7 +161,7 @@ mod drain_and_fill_tests{
         // println!("{reader1}");
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!(msg.len() + SZ_OF_USIZE, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!(msg.len() + LenWidth::default().bytes(), amt); // Verify drain_and_fill wrote the bytes
         // println!("{writer2}");
 
         assert!(reader1.is_empty()); // Verify reader1 is empty
@@ -182,9 +184,9 @@ mod drain_and_fill_tests{
         let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
 
         // Advance the head and tail of the reader to 4 bytes before the end to force a msg to wrap
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 4; 
-        reader1.set_head(buffer_end);
-        writer1.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 4; 
+        reader1.set_head(buffer_end as u64);
+        writer1.set_tail(buffer_end as u64);
         
         assert!(reader1.is_empty());
         assert!(writer1.is_empty());
@@ -199,7 +201,7 @@ mod drain_and_fill_tests{
         // println!("{reader1}");
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!(msg.len() + SZ_OF_USIZE, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!(msg.len() + LenWidth::default().bytes(), amt); // Verify drain_and_fill wrote the bytes
         // println!("{writer2}");
 
         assert!(reader1.is_empty()); // Verify reader1 is empty
@@ -223,17 +225,17 @@ mod drain_and_fill_tests{
         let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
 
         // Advance the head and tail of the reader to 8 bytes before the end to force a msg to wrap
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 8; 
-        reader1.set_head(buffer_end);
-        writer1.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 8; 
+        reader1.set_head(buffer_end as u64);
+        writer1.set_tail(buffer_end as u64);
         
         assert!(reader1.is_empty());
         assert!(writer1.is_empty());
 
         // Advance the head and tail of the reader to 4 bytes before the end to force a msg to wrap
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 4; 
-        reader2.set_head(buffer_end);
-        writer2.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 4; 
+        reader2.set_head(buffer_end as u64);
+        writer2.set_tail(buffer_end as u64);
 
         assert!(reader2.is_empty());
         assert!(writer2.is_empty());
@@ -245,7 +247,7 @@ mod drain_and_fill_tests{
         // println!("{reader1}");
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!(msg.len() + SZ_OF_USIZE, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!(msg.len() + LenWidth::default().bytes(), amt); // Verify drain_and_fill wrote the bytes
         // println!("{writer2}");
 
         assert!(reader1.is_empty()); // Verify reader1 is empty
@@ -269,17 +271,17 @@ mod drain_and_fill_tests{
         let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
 
         // Advance the head and tail of the reader to 8 bytes before the end to force a msg to wrap
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 8; 
-        reader1.set_head(buffer_end);
-        writer1.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 8; 
+        reader1.set_head(buffer_end as u64);
+        writer1.set_tail(buffer_end as u64);
         
         assert!(reader1.is_empty());
         assert!(writer1.is_empty());
 
         // Advance the head and tail of the reader to 4 bytes before the end to force a msg to wrap
-        let buffer_end = TEST_SHM_SIZE - 2 * SZ_OF_USIZE - 12; 
-        reader2.set_head(buffer_end);
-        writer2.set_tail(buffer_end);
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 12; 
+        reader2.set_head(buffer_end as u64);
+        writer2.set_tail(buffer_end as u64);
 
         assert!(reader2.is_empty());
         assert!(writer2.is_empty());
@@ -291,7 +293,7 @@ mod drain_and_fill_tests{
         // println!("{reader1}");
 
         let amt = drain_and_fill(&mut reader1, &mut writer2);
-        assert_eq!(msg.len() + SZ_OF_USIZE, amt); // Verify drain_and_fill wrote the bytes
+        assert_eq!(msg.len() + LenWidth::default().bytes(), amt); // Verify drain_and_fill wrote the bytes
         // println!("{writer2}");
 
         assert!(reader1.is_empty()); // Verify reader1 is empty
@@ -302,4 +304,158 @@ mod drain_and_fill_tests{
         assert_eq!(msg, &buffer3[..amt]);
     }
 
-}
\ No newline at end of file
+    /// Verifies zero length messages are transferred instead of being mistaken for the end of the data
+    #[test]
+    fn zero_length_msgs(){
+        let mut buffer1: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader1 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        let mut writer1 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+
+        let mut buffer2: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader2 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+        let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+
+        // A heartbeat, a msg, and another heartbeat
+        let msg = b"AAAA";
+        let _amt = writer1.push(b"");
+        let _amt = writer1.push(msg);
+        let _amt = writer1.push(b"");
+
+        let amt = drain_and_fill(&mut reader1, &mut writer2);
+        assert_eq!(msg.len() + 3 * LenWidth::default().bytes(), amt); // Verify all three were transferred
+        assert!(reader1.is_empty());
+
+        let mut buffer3: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        assert_eq!(Ok(0), reader2.try_pop(&mut buffer3));
+        assert_eq!(Ok(msg.len()), reader2.try_pop(&mut buffer3));
+        assert_eq!(msg, &buffer3[..msg.len()]);
+        assert_eq!(Ok(0), reader2.try_pop(&mut buffer3));
+        assert_eq!(Err(RingError::Empty), reader2.try_pop(&mut buffer3));
+    }
+
+    /// Verifies messages are reframed when moving from a contiguous ring to a plain one
+    #[test]
+    fn contiguous_to_plain(){
+        let mut buffer1: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader1 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        let mut writer1 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        reader1.set_contiguous(true);
+        writer1.set_contiguous(true);
+
+        let mut buffer2: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader2 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+        let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+
+        // Force the writer to wrap so the copy lands in two parts
+        let buffer_end = TEST_SHM_SIZE - CONTROL_SIZE - 12;
+        reader2.set_head(buffer_end as u64);
+        writer2.set_tail(buffer_end as u64);
+
+        let msg = b"AAAAB";
+        let _amt = writer1.push(msg);
+        let _amt = writer1.push(b"");
+
+        let amt = drain_and_fill(&mut reader1, &mut writer2);
+        assert_eq!(msg.len() + 2 * LenWidth::default().bytes(), amt); // Verify the padding was dropped
+        assert!(reader1.is_empty());
+
+        let mut buffer3: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        assert_eq!(Ok(msg.len()), reader2.try_pop(&mut buffer3));
+        assert_eq!(msg, &buffer3[..msg.len()]);
+        assert_eq!(Ok(0), reader2.try_pop(&mut buffer3));
+        assert!(writer2.is_empty());
+    }
+
+    /// Verifies messages from a lossy reader are copied out one at a time, and only as many as fit
+    #[test]
+    fn lossy_reader(){
+        let mut buffer1: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader1 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        let mut writer1 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        reader1.set_lossy(true);
+        writer1.set_lossy(true);
+
+        let mut buffer2: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader2 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+        let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+        writer2.push(b"AAAABBBB");
+
+        for msg in [b"CCCC", b"DDDD", b"EEEE", b"FFFF"] {
+            writer1.push(msg);
+        }
+        assert_eq!(1, writer1.get_dropped());
+
+        let amt = drain_and_fill(&mut reader1, &mut writer2);
+        assert_eq!(4 + LenWidth::default().bytes(), amt); // only one fits next to the message already in the writer
+        assert_eq!(Ok(4), reader1.peek_len());
+
+        let mut buffer3: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        assert_eq!(Ok(8), reader2.try_pop(&mut buffer3));
+        assert_eq!(Ok(4), reader2.try_pop(&mut buffer3));
+        assert_eq!(b"DDDD", &buffer3[..4]);
+        assert_eq!(Err(RingError::Empty), reader2.try_pop(&mut buffer3));
+    }
+
+    /// Verifies a lossy contiguous reader is copied out through the lossy path and its padding is dropped
+    #[test]
+    fn lossy_contiguous_reader(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer1: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer1.as_mut_ptr() as *mut u8;
+        let mut reader1 = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut writer1 = unsafe{ RingbufRw::new(SIZE, ptr) };
+        reader1.set_contiguous(true);
+        writer1.set_contiguous(true);
+        reader1.set_lossy(true);
+        writer1.set_lossy(true);
+
+        let mut buffer2: Vec<u8> = vec![0;SIZE];
+        let mut reader2 = unsafe{ RingbufRo::new(SIZE, buffer2.as_mut_ptr()) };
+        let mut writer2 = unsafe{ RingbufRw::new(SIZE, buffer2.as_mut_ptr()) };
+
+        // the last push wraps, dropping two messages and padding the end of the buffer
+        for i in 0..3u8 {
+            writer1.push(&[i;8]);
+        }
+        writer1.push(&[3;16]);
+        assert_eq!(2, writer1.get_dropped());
+
+        let amt = drain_and_fill(&mut reader1, &mut writer2);
+        assert_eq!(8 + 16 + 2 * LenWidth::default().bytes(), amt);
+        assert!(reader1.is_empty());
+
+        let mut buffer3 = [0;16];
+        assert_eq!(Ok(8), reader2.try_pop(&mut buffer3));
+        assert_eq!([2;8], buffer3[..8]);
+        assert_eq!(Ok(16), reader2.try_pop(&mut buffer3));
+        assert_eq!([3;16], buffer3);
+        assert_eq!(Err(RingError::Empty), reader2.try_pop(&mut buffer3));
+    }
+
+    /// Verifies messages are reframed when the two rings use different length fields
+    #[test]
+    fn different_len_widths(){
+        let mut buffer1: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader1 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        let mut writer1 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer1.as_mut_ptr()) };
+        reader1.set_len_width(LenWidth::U16);
+        writer1.set_len_width(LenWidth::U16);
+
+        let mut buffer2: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut reader2 = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+        let mut writer2 = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer2.as_mut_ptr()) };
+
+        assert_eq!(7, writer1.push(b"AAAAB"));
+        assert_eq!(2, writer1.push(b""));
+
+        let amt = drain_and_fill(&mut reader1, &mut writer2);
+        assert_eq!(5 + 2 * LenWidth::default().bytes(), amt);
+        assert!(reader1.is_empty());
+
+        let mut buffer3: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        assert_eq!(Ok(5), reader2.try_pop(&mut buffer3));
+        assert_eq!(b"AAAAB", &buffer3[..5]);
+        assert_eq!(Ok(0), reader2.try_pop(&mut buffer3));
+        assert!(writer2.is_empty());
+    }
+}
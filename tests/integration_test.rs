@@ -1,7 +1,9 @@
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests{
-    use shm_ring::{ringbuffer_ro::RingbufRo, ringbuffer_rw::RingbufRw};
-    const TEST_SHM_SIZE: usize = 52;//8 for head, 8 for tail, 36 for buffer (35 that are available)
+    use std::time::{Duration, Instant};
+    use shm_ring::{CACHE_LINE, CONTROL_SIZE, LenWidth, error::RingError, ringbuffer_ro::RingbufRo, ringbuffer_rw::RingbufRw};
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 36;//tail, head and wait words, 36 for buffer
 
 
     #[test]
@@ -10,7 +12,7 @@ mod tests{
         let r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
         let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
 
-        let msg = b"AAAABBBBCCCCDDDDEEEEFFFFGGG";
+        let msg = b"AAAABBBBCCCCDDDDEEEEFFFFGGGG";
         let _result = w_ring.push(msg);
 
         assert_eq!(true, r_ring.is_full());
@@ -46,7 +48,7 @@ mod tests{
         assert_eq!(14, w_ring.get_tail());
         assert_eq!(36, w_ring.get_size());
         assert_eq!(14, w_ring.get_curr_bytes());
-        assert_eq!(21, w_ring.empty_slots_left());
+        assert_eq!(22, w_ring.empty_slots_left());
         assert_eq!(false, w_ring.is_empty());
         assert_eq!(false, w_ring.is_full());
 
@@ -60,7 +62,7 @@ mod tests{
         assert_eq!(14, r_ring.get_tail());
         assert_eq!(36, r_ring.get_size());
         assert_eq!(0, r_ring.get_curr_bytes());
-        assert_eq!(35, r_ring.empty_slots_left());
+        assert_eq!(36, r_ring.empty_slots_left());
         assert_eq!(true, r_ring.is_empty());
         assert_eq!(false, r_ring.is_full());
 
@@ -72,7 +74,7 @@ mod tests{
         assert_eq!(28, w_ring.get_tail());
         assert_eq!(36, w_ring.get_size());
         assert_eq!(14, w_ring.get_curr_bytes());
-        assert_eq!(21, w_ring.empty_slots_left());
+        assert_eq!(22, w_ring.empty_slots_left());
         assert_eq!(false, w_ring.is_empty());
         assert_eq!(false, w_ring.is_full());
 
@@ -87,7 +89,7 @@ mod tests{
         assert_eq!(28, r_ring.get_tail());
         assert_eq!(36, r_ring.get_size());
         assert_eq!(0, r_ring.get_curr_bytes());
-        assert_eq!(35, r_ring.empty_slots_left());
+        assert_eq!(36, r_ring.empty_slots_left());
         assert_eq!(true, r_ring.is_empty());
         assert_eq!(false, r_ring.is_full());
     }
@@ -98,7 +100,7 @@ mod tests{
         let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
         let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
 
-        let msg = b"AAAABBBBCC";
+        let msg = b"AAAABBBBCCC";
         println!("PUSH {:x?}", &msg);
         let _result = w_ring.push(msg);
         println!("{w_ring}");
@@ -111,7 +113,7 @@ mod tests{
         assert_eq!(true, w_ring.is_full());
 
 
-        let mut buffer = [0;10];
+        let mut buffer = [0;11];
         let _result = r_ring.pop(&mut buffer);
         println!("POP {:x?}", &buffer);
         println!("{r_ring}");
@@ -285,4 +287,772 @@ mod tests{
         assert_eq!(0, result);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_push_pop_across_threads(){
+        const MSGS: usize = 10_000;
+        let mut buffer: Vec<u64> = vec![0;512];
+        let size = buffer.len() * 8;
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(size, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(size, ptr) };
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    let msg = (i as u32).to_le_bytes();
+                    let len = i % msg.len() + 1;
+                    while w_ring.push(&msg[..len]) == 0 {
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+            s.spawn(move || {
+                let mut buffer = [0;4];
+                for i in 0..MSGS {
+                    let expected = (i as u32).to_le_bytes();
+                    let len = i % expected.len() + 1;
+                    let mut sz = r_ring.pop(&mut buffer);
+                    while sz == 0 {
+                        std::hint::spin_loop();
+                        sz = r_ring.pop(&mut buffer);
+                    }
+                    assert_eq!(len, sz);
+                    assert_eq!(&expected[..len], &buffer[..sz]);
+                }
+                assert!(r_ring.is_empty());
+            });
+        });
+    }
+
+    #[test]
+    fn test_blocking_across_threads(){
+        const MSGS: usize = 10_000;
+        // a small ring, so both sides keep running into it being full or empty and have to sleep
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    let msg = (i as u32).to_le_bytes();
+                    assert_eq!(Ok(12), w_ring.push_blocking(&msg, None));
+                }
+            });
+            s.spawn(move || {
+                let mut buffer = [0;4];
+                for i in 0..MSGS {
+                    assert_eq!(Ok(4), r_ring.pop_blocking(&mut buffer, None));
+                    assert_eq!((i as u32).to_le_bytes(), buffer);
+                }
+                assert!(r_ring.is_empty());
+            });
+        });
+    }
+
+    #[test]
+    fn test_blocking_timeouts(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let timeout = Duration::from_millis(20);
+
+        let mut dst = [0;TEST_SHM_SIZE];
+        let start = Instant::now();
+        assert_eq!(Err(RingError::Empty), r_ring.pop_blocking(&mut dst, Some(timeout)));
+        assert!(start.elapsed() >= timeout);
+
+        let msg = b"AAAABBBBCCCCDDDD";
+        assert_eq!(Ok(24), w_ring.push_blocking(msg, Some(timeout)));
+        let start = Instant::now();
+        assert_eq!(Err(RingError::Full), w_ring.push_blocking(msg, Some(timeout)));
+        assert!(start.elapsed() >= timeout);
+
+        // errors that waiting cannot fix come back right away
+        assert_eq!(Err(RingError::MessageTooLarge { len: 29, max: 28 }), w_ring.push_blocking(&[0;29], None));
+        assert_eq!(Ok(16), r_ring.pop_blocking(&mut dst, Some(timeout)));
+        assert_eq!(msg, &dst[..16]);
+    }
+
+    #[test]
+    fn test_try_push_errors(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        let msg = b"AAAABBBBCCCCDDDDEEEEFFFFGGGGH";
+        assert_eq!(Err(RingError::MessageTooLarge { len: 29, max: 28 }), w_ring.try_push(msg));
+        assert!(w_ring.is_empty());
+
+        let msg = b"AAAABBBBCCCCDDDD";
+        assert_eq!(Ok(24), w_ring.try_push(msg));
+        assert_eq!(Err(RingError::Full), w_ring.try_push(msg));
+        assert_eq!(24, w_ring.get_tail());
+    }
+
+    #[test]
+    fn test_try_pop_errors(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        let mut dst = [0;6];
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+
+        let msg = b"AAAABBBB";
+        w_ring.push(msg);
+        assert_eq!(Err(RingError::DestinationTooSmall { needed: 8, available: 6 }), r_ring.try_pop(&mut dst));
+        assert_eq!(0, r_ring.get_head()); // the message was left in the ring
+
+        let mut dst = [0;8];
+        assert_eq!(Ok(8), r_ring.try_pop(&mut dst));
+        assert_eq!(msg, &dst);
+    }
+
+    #[test]
+    fn test_try_pop_corrupted(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let ptr = buffer.as_mut_ptr();
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+
+        w_ring.push(b"AAAA");
+        // overwrite the length field that follows the control block
+        unsafe { ptr.add(CONTROL_SIZE).write(100) };
+
+        let mut dst = [0;TEST_SHM_SIZE];
+        assert_eq!(Err(RingError::Corrupted { head: 0, tail: 12, len: 100 }), r_ring.try_pop(&mut dst));
+        assert_eq!(0, r_ring.get_head());
+    }
+
+    #[test]
+    fn test_empty_msg_is_not_an_empty_ring(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        assert_eq!(Ok(8), w_ring.try_push(b""));
+        assert_eq!(Ok(8), w_ring.try_push(b""));
+        assert!(!r_ring.is_empty());
+
+        let mut dst = [0;0];
+        assert_eq!(Ok(0), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(0), r_ring.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_pop_into_short_buffer(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        assert_eq!(Err(RingError::Empty), r_ring.peek_len());
+
+        let msg = b"AAAABBBBCC";
+        w_ring.push(msg);
+        assert_eq!(Ok(msg.len()), r_ring.peek_len());
+
+        // the buffer is too small, so nothing is consumed
+        let mut dst = [0;4];
+        assert_eq!(0, r_ring.pop(&mut dst));
+        assert_eq!(0, r_ring.get_head());
+        assert_eq!(Ok(msg.len()), r_ring.peek_len());
+
+        // size the buffer from peek_len and try again
+        let mut dst = vec![0;r_ring.peek_len().unwrap()];
+        assert_eq!(msg.len(), r_ring.pop(&mut dst));
+        assert_eq!(msg, &dst[..]);
+        assert_eq!(Err(RingError::Empty), r_ring.peek_len());
+    }
+
+    #[test]
+    fn test_read_in_place(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        assert_eq!(Err(RingError::Empty), r_ring.read().map(|msg| msg.len()));
+
+        let msg = b"AAAABB";
+        w_ring.push(msg);
+        let guard = r_ring.read().unwrap();
+        assert_eq!((&msg[..], None), guard.parts());
+        assert_eq!(msg.len(), guard.len());
+        assert_eq!(Ok(()), guard.commit());
+        assert_eq!(14, r_ring.get_head());
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_read_wrapped_msg(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // start 12 bytes before the end so the payload wraps
+        r_ring.set_head(24);
+        w_ring.set_tail(24);
+
+        let msg = b"AAAABBBB";
+        w_ring.push(msg);
+        {
+            let guard = r_ring.read().unwrap();
+            assert_eq!((&msg[..4], Some(&msg[4..])), guard.parts());
+            assert_eq!(msg.to_vec(), guard.to_vec());
+        }
+        // the guard was dropped, which consumes the message
+        assert_eq!(40, r_ring.get_head());
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_reserve_in_place(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        assert_eq!(Err(RingError::MessageTooLarge { len: 29, max: 28 }), w_ring.reserve(29).map(|grant| grant.len()));
+
+        let mut grant = w_ring.reserve(6).unwrap();
+        let (first_part, second_part) = grant.parts_mut();
+        assert!(second_part.is_none());
+        first_part.copy_from_slice(b"AAAABB");
+        // nothing is published until the grant is committed
+        assert!(r_ring.is_empty());
+        grant.commit();
+
+        let mut dst = [0;6];
+        assert_eq!(Ok(6), r_ring.try_pop(&mut dst));
+        assert_eq!(b"AAAABB", &dst);
+    }
+
+    #[test]
+    fn test_reserve_wrapped_and_abandoned(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // start 12 bytes before the end so the payload wraps
+        r_ring.set_head(24);
+        w_ring.set_tail(24);
+
+        // dropping a grant without committing it leaves the ring untouched
+        {
+            let mut grant = w_ring.reserve(8).unwrap();
+            grant.copy_from_slice(b"XXXXXXXX");
+        }
+        assert_eq!(24, w_ring.get_tail());
+        assert!(r_ring.is_empty());
+
+        let msg = b"AAAABBBB";
+        let mut grant = w_ring.reserve(msg.len()).unwrap();
+        let (first_part, second_part) = grant.parts_mut();
+        assert_eq!(4, first_part.len());
+        assert_eq!(Some(4), second_part.map(|part| part.len()));
+        grant.copy_from_slice(msg);
+        grant.commit();
+        assert_eq!(40, w_ring.get_tail());
+
+        let mut dst = [0;8];
+        assert_eq!(Ok(8), r_ring.try_pop(&mut dst));
+        assert_eq!(msg, &dst);
+    }
+
+    #[test]
+    fn test_contiguous_wrap_with_padding(){
+        const SIZE: usize = CONTROL_SIZE + 88;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+
+        // records are rounded up so every length field stays aligned
+        assert_eq!(Ok(16), w_ring.try_push(b"AAAAB"));
+        let mut dst = [0;16];
+        assert_eq!(Ok(5), r_ring.try_pop(&mut dst));
+        assert_eq!(16, r_ring.get_head());
+
+        let msg = b"AAAABBBBCCCCDDDD";
+        assert_eq!(Ok(24), w_ring.try_push(msg));
+        assert_eq!(Ok(24), w_ring.try_push(msg));
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!(64, w_ring.get_tail());
+
+        // only 24 bytes are left before the end, so the record skips them and starts over
+        let msg2 = b"EEEEFFFFGGGGHHHHI";
+        assert_eq!(Ok(32), w_ring.try_push(msg2));
+        assert_eq!(120, w_ring.get_tail());
+        assert_eq!(u64::MAX, buffer[(CONTROL_SIZE + 64)/8]);
+
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!(msg, &dst);
+        let guard = r_ring.read().unwrap();
+        assert_eq!(Some(&msg2[..]), guard.as_slice());
+        assert_eq!(Ok(()), guard.commit());
+        assert_eq!(120, r_ring.get_head());
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_contiguous_publishes_padding_alone(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+
+        // 36 bytes hold at most a 32 byte record
+        assert_eq!(Err(RingError::MessageTooLarge { len: 25, max: 24 }), w_ring.try_push(&[0;25]));
+
+        assert_eq!(Ok(24), w_ring.try_push(b"AAAABBBBCCCC"));
+        let mut dst = [0;24];
+        assert_eq!(Ok(12), r_ring.try_pop(&mut dst));
+
+        // the ring is empty but the record only fits from the start, so just the padding is published
+        let msg = b"AAAABBBBCCCCDDDDEEEEFFFF";
+        assert_eq!(Err(RingError::Full), w_ring.try_push(msg));
+        assert_eq!(36, w_ring.get_tail());
+        // the reader skips the padding without finding a message
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+        assert_eq!(36, r_ring.get_head());
+
+        assert_eq!(Ok(32), w_ring.try_push(msg));
+        assert_eq!(Ok(24), r_ring.try_pop(&mut dst));
+        assert_eq!(msg, &dst);
+    }
+
+    #[test]
+    fn test_lossy_drops_oldest(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        r_ring.set_lossy(true);
+        w_ring.set_lossy(true);
+
+        assert_eq!(Ok(12), w_ring.try_push(b"AAAA"));
+        assert_eq!(Ok(12), w_ring.try_push(b"BBBB"));
+        assert_eq!(Ok(12), w_ring.try_push(b"CCCC"));
+        assert_eq!(0, w_ring.get_dropped());
+        // the ring was full, the new message goes over where the two oldest ones were
+        assert_eq!(Ok(20), w_ring.try_push(b"DDDDEEEEFFFF"));
+        assert_eq!(2, w_ring.get_dropped());
+        assert_eq!(Err(RingError::MessageTooLarge { len: 29, max: 28 }), w_ring.try_push(&[0;29]));
+
+        let mut dst = [0;12];
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"CCCC", &dst[..4]);
+        assert_eq!(Ok(12), r_ring.try_pop(&mut dst));
+        assert_eq!(b"DDDDEEEEFFFF", &dst);
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+    }
+
+    #[test]
+    fn test_lossy_contiguous(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+        r_ring.set_lossy(true);
+        w_ring.set_lossy(true);
+
+        for i in 0..3u8 {
+            assert_eq!(Ok(16), w_ring.try_push(&[i;8]));
+        }
+        // 16 bytes are left before the end but none are free. one message goes to make room
+        // for the padding and one more to fit the record at the start
+        assert_eq!(Ok(24), w_ring.try_push(&[3;16]));
+        assert_eq!(2, w_ring.get_dropped());
+        assert_eq!(88, w_ring.get_tail());
+
+        let mut dst = [0;16];
+        assert_eq!(Ok(8), r_ring.try_pop(&mut dst));
+        assert_eq!([2;8], dst[..8]);
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!([3;16], dst);
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_lossy_read_lapped(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        r_ring.set_lossy(true);
+        w_ring.set_lossy(true);
+
+        assert_eq!(Ok(12), w_ring.try_push(b"AAAA"));
+        let guard = r_ring.read().unwrap();
+        assert_eq!(b"AAAA".to_vec(), guard.to_vec());
+        // the producer drops the borrowed message to make room and writes over it
+        for msg in [b"BBBB", b"CCCC", b"DDDD"] {
+            assert_eq!(Ok(12), w_ring.try_push(msg));
+        }
+        assert_eq!(1, w_ring.get_dropped());
+        assert_eq!(Err(RingError::Lapped { skipped: 12 }), guard.commit());
+
+        let mut dst = [0;4];
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"BBBB", &dst);
+    }
+
+    #[test]
+    fn test_lossy_across_threads(){
+        const MSGS: u32 = 20_000;
+        let size = CONTROL_SIZE + 128;
+        let mut buffer: Vec<u64> = vec![0;size.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(size, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(size, ptr) };
+        r_ring.set_lossy(true);
+        w_ring.set_lossy(true);
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    let mut msg = [i.to_le_bytes(), i.to_le_bytes()].concat();
+                    msg.resize(8 + i as usize % 7, i as u8);
+                    assert_eq!(msg.len() + 8, w_ring.push(&msg));
+                }
+            });
+            s.spawn(move || {
+                let mut dst = [0;16];
+                let mut last = None;
+                while last != Some(MSGS - 1) {
+                    // dropped messages are looked past, they never show up as corruption
+                    assert!(!matches!(r_ring.peek_len(), Err(RingError::Corrupted { .. })));
+                    let Ok(len) = r_ring.try_pop(&mut dst) else {
+                        std::thread::yield_now();
+                        continue;
+                    };
+                    // whatever was dropped, a message that comes out is whole and newer than the last one
+                    let i = u32::from_le_bytes(dst[..4].try_into().unwrap());
+                    assert_eq!(dst[..4], dst[4..8]);
+                    assert_eq!(8 + i as usize % 7, len);
+                    assert!(dst[8..len].iter().all(|&byte| byte == i as u8));
+                    assert!(last.is_none_or(|last| last < i));
+                    last = Some(i);
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn test_len_width_u16(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        r_ring.set_len_width(LenWidth::U16);
+        w_ring.set_len_width(LenWidth::U16);
+
+        let mut dst = [0;36];
+        assert_eq!(Ok(8), w_ring.try_push(b"AAAABB"));
+        assert_eq!(Ok(6), r_ring.peek_len());
+        assert_eq!(Ok(6), r_ring.try_pop(&mut dst));
+        assert_eq!(b"AAAABB", &dst[..6]);
+
+        // the length field itself wraps the end of the buffer
+        r_ring.set_head(35);
+        w_ring.set_tail(35);
+        assert_eq!(Ok(6), w_ring.try_push(b"CCCC"));
+        assert_eq!(41, w_ring.get_tail());
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"CCCC", &dst[..4]);
+
+        assert_eq!(Err(RingError::MessageTooLarge { len: 35, max: 34 }), w_ring.try_push(&[0;35]));
+        assert_eq!(Ok(36), w_ring.try_push(&[7;34]));
+        assert_eq!(Ok(34), r_ring.try_pop(&mut dst));
+        assert!(dst[..34].iter().all(|&byte| byte == 7));
+
+        // the field limits the message, however big the ring is
+        let size = CONTROL_SIZE + 70_000;
+        let mut buffer: Vec<u8> = vec![0;size];
+        let mut w_ring = unsafe{ RingbufRw::new(size, buffer.as_mut_ptr()) };
+        w_ring.set_len_width(LenWidth::U16);
+        assert_eq!(Err(RingError::MessageTooLarge { len: 65_535, max: 65_534 }), w_ring.try_push(&[0;65_535]));
+        assert_eq!(Ok(65_536), w_ring.try_push(&[0;65_534]));
+    }
+
+    #[test]
+    fn test_len_width_contiguous_u32(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+        r_ring.set_len_width(LenWidth::U32);
+        w_ring.set_len_width(LenWidth::U32);
+
+        // records are aligned to the 4 byte field
+        let mut dst = [0;40];
+        assert_eq!(Ok(12), w_ring.try_push(b"AAAAB"));
+        assert_eq!(Ok(44), w_ring.try_push(&[1;40]));
+        assert_eq!(Ok(5), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(40), r_ring.try_pop(&mut dst));
+        assert_eq!(56, r_ring.get_head());
+
+        // does not fit in the last 8 bytes, which are skipped with a 4 byte marker
+        assert_eq!(Ok(12), w_ring.try_push(b"CCCCCCCC"));
+        assert_eq!(0xFFFF_FFFF, buffer[(CONTROL_SIZE + 56)/8]);
+        assert_eq!(Ok(8), r_ring.try_pop(&mut dst));
+        assert_eq!(b"CCCCCCCC", &dst[..8]);
+        assert_eq!(76, r_ring.get_head());
+    }
+
+    #[test]
+    fn test_push_batch(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // only whole messages go in, the third one does not fit
+        assert_eq!(2, w_ring.push_batch(&[b"AAAA", b"BBBB", b"CCCCC"]));
+        assert_eq!(24, w_ring.get_tail());
+        let mut dst = [0;12];
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"AAAA", &dst[..4]);
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"BBBB", &dst[..4]);
+
+        // the batch wraps the end of the buffer and stops at the first message that does not fit
+        let mut msgs = ["CCCC", "DDDDDD", "EE", "F"].into_iter().peekable();
+        assert_eq!(3, w_ring.push_iter(&mut msgs));
+        assert_eq!(Some("F"), msgs.next());
+        assert_eq!(60, w_ring.get_tail());
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"CCCC", &dst[..4]);
+        assert_eq!(Ok(6), r_ring.try_pop(&mut dst));
+        assert_eq!(b"DDDDDD", &dst[..6]);
+        assert_eq!(Ok(2), r_ring.try_pop(&mut dst));
+        assert_eq!(b"EE", &dst[..2]);
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+
+        assert_eq!(0, w_ring.push_batch(&[&[0;29]]));
+        assert_eq!(0, w_ring.push_batch(&[]));
+        assert!(w_ring.is_empty());
+    }
+
+    #[test]
+    fn test_push_iter_resumes(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // only three fit at a time, the fourth has to still be there for the second call
+        let mut msgs = (0..6u32).map(u32::to_le_bytes).peekable();
+        let mut received = Vec::new();
+        let mut dst = [0;4];
+        for _ in 0..2 {
+            assert_eq!(3, w_ring.push_iter(msgs.by_ref()));
+            while let Ok(4) = r_ring.try_pop(&mut dst) {
+                received.push(u32::from_le_bytes(dst));
+            }
+        }
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], received);
+        assert!(msgs.next().is_none());
+    }
+
+    #[test]
+    fn test_push_batch_contiguous(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+
+        let mut dst = [0;16];
+        assert_eq!(2, w_ring.push_batch(&[&[1;16], &[2;16]]));
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+
+        // the first message skips the last 16 bytes, then the ring is too full for the second
+        assert_eq!(1, w_ring.push_batch(&[&[3;16], &[4;24]]));
+        assert_eq!(88, w_ring.get_tail());
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!([3;16], dst);
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+    }
+
+    #[test]
+    fn test_push_batch_lossy(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        r_ring.set_lossy(true);
+        w_ring.set_lossy(true);
+
+        // a batch bigger than the ring drops its own oldest messages once they are published
+        assert_eq!(5, w_ring.push_batch(&[b"AAAA", b"BBBB", b"CCCC", b"DDDD", b"EEEE"]));
+        assert_eq!(2, w_ring.get_dropped());
+
+        let mut dst = [0;12];
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"CCCC", &dst[..4]);
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"DDDD", &dst[..4]);
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(b"EEEE", &dst[..4]);
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+    }
+
+    #[test]
+    fn test_pop_batch(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        let mut arena = [0;16];
+        let mut offsets = [0;4];
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop_batch(&mut arena, &mut offsets));
+        assert_eq!(3, w_ring.push_batch(&[b"AAAA", b"BB", b""]));
+
+        // limited by the number of offsets
+        assert_eq!(Ok(2), r_ring.try_pop_batch(&mut arena, &mut offsets[..2]));
+        assert_eq!([4, 6], offsets[..2]);
+        assert_eq!(b"AAAABB", &arena[..6]);
+        assert_eq!(22, r_ring.get_head());
+
+        // limited by the size of the arena, the message that does not fit is left in the ring
+        assert_eq!(14, w_ring.push(b"CCCCCC"));
+        assert_eq!(Ok(1), r_ring.try_pop_batch(&mut arena[..5], &mut offsets));
+        assert_eq!(0, offsets[0]);
+        assert_eq!(Err(RingError::DestinationTooSmall { needed: 6, available: 5 }), r_ring.try_pop_batch(&mut arena[..5], &mut offsets));
+
+        // the length field of this one wraps the end of the buffer
+        assert_eq!(Ok(1), r_ring.try_pop_batch(&mut arena, &mut offsets));
+        assert_eq!(6, offsets[0]);
+        assert_eq!(b"CCCCCC", &arena[..6]);
+        assert_eq!(0, r_ring.pop_batch(&mut arena, &mut offsets));
+        assert_eq!(Ok(0), r_ring.try_pop_batch(&mut arena, &mut []));
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_pop_batch_contiguous(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+        r_ring.set_contiguous(true);
+        w_ring.set_contiguous(true);
+
+        let mut arena = [0;64];
+        let mut offsets = [0;4];
+        assert_eq!(1, w_ring.push_batch(&[&[1;32]]));
+        assert_eq!(1, r_ring.pop_batch(&mut arena, &mut offsets));
+
+        // the padding between the two messages is skipped within the batch
+        assert_eq!(2, w_ring.push_batch(&[&[2;8], &[3;16]]));
+        assert_eq!(2, r_ring.pop_batch(&mut arena, &mut offsets));
+        assert_eq!([8, 24], offsets[..2]);
+        assert_eq!([2;8], arena[..8]);
+        assert_eq!([3;16], arena[8..24]);
+        assert_eq!(88, r_ring.get_head());
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_control_block_layout(){
+        const SIZE: usize = CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(SIZE, ptr) };
+
+        let mut dst = [0;8];
+        assert_eq!(Ok(16), w_ring.try_push(b"AAAABBBB"));
+        assert_eq!(Ok(16), w_ring.try_push(b"CCCCDDDD"));
+        assert_eq!(Ok(8), r_ring.try_pop(&mut dst));
+        drop((r_ring, w_ring));
+
+        // the tail, the head and the buffer each start a cache line of their own
+        assert_eq!(32, buffer[0]);
+        assert_eq!(16, buffer[CACHE_LINE/8]);
+        assert_eq!(8, buffer[CONTROL_SIZE/8]);
+        assert_eq!(u64::from_le_bytes(*b"AAAABBBB"), buffer[CONTROL_SIZE/8 + 1]);
+        assert!(buffer[1..CACHE_LINE/8].iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn test_cached_positions(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // each side picks up the other's progress once its copy of it runs out
+        let mut dst = [0;12];
+        assert_eq!(Ok(12), w_ring.try_push(b"AAAA"));
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Empty), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(12), w_ring.try_push(b"BBBB"));
+        assert_eq!(Ok(12), w_ring.try_push(b"CCCC"));
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(12), w_ring.try_push(b"DDDD"));
+        assert_eq!(Ok(12), w_ring.try_push(b"EEEE"));
+        assert_eq!(Err(RingError::Full), w_ring.try_push(b"FFFF"));
+        assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+        assert_eq!(Ok(12), w_ring.try_push(b"FFFF"));
+        for msg in [b"DDDD", b"EEEE", b"FFFF"] {
+            assert_eq!(Ok(4), r_ring.try_pop(&mut dst));
+            assert_eq!(msg, &dst[..4]);
+        }
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_positions_count_every_byte(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+
+        // the head and the tail keep counting past the end of the buffer
+        let mut dst = [0;10];
+        for i in 0..100u8 {
+            assert_eq!(18, w_ring.push(&[i;10]));
+            assert_eq!(18, w_ring.push(&[i;10]));
+            assert!(w_ring.is_full());
+            assert_eq!(10, r_ring.pop(&mut dst));
+            assert_eq!(10, r_ring.pop(&mut dst));
+            assert_eq!([i;10], dst);
+        }
+        assert_eq!(3600, w_ring.get_tail());
+        assert_eq!(3600, r_ring.get_head());
+        assert!(r_ring.is_empty());
+    }
+
+    #[test]
+    fn test_new_pow2(){
+        const SIZE: usize = CONTROL_SIZE + 32;
+        let mut buffer: Vec<u64> = vec![0;SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new_pow2(SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new_pow2(SIZE, ptr) };
+
+        // the length field and the payload both wrap the end of the buffer
+        r_ring.set_head(28);
+        w_ring.set_tail(28);
+        let mut dst = [0;16];
+        assert_eq!(Ok(24), w_ring.try_push(b"AAAABBBBCCCCDDDD"));
+        assert_eq!(Ok(16), r_ring.try_pop(&mut dst));
+        assert_eq!(b"AAAABBBBCCCCDDDD", &dst);
+        assert_eq!(52, r_ring.get_head());
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn test_new_pow2_rejects_other_sizes(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let _w_ring = unsafe{ RingbufRw::new_pow2(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod mpsc_tests{
+    use shm_ring::{
+            error::RingError,
+            mpsc::{MPSC_CONTROL_SIZE, MpscConsumer, MpscProducer},
+    };
+    const TEST_SHM_SIZE: usize = MPSC_CONTROL_SIZE + 64;
+
+    /// Verifies messages come out in order with their records rounded up to 8 bytes
+    #[test]
+    fn push_and_pop(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let producer = unsafe{ MpscProducer::new(TEST_SHM_SIZE, ptr) };
+        let mut consumer = unsafe{ MpscConsumer::new(TEST_SHM_SIZE, ptr) };
+        assert_eq!(64, consumer.get_size());
+        assert!(consumer.is_empty());
+
+        let mut dst = [0;64];
+        assert_eq!(Err(RingError::Empty), consumer.try_pop(&mut dst));
+        assert_eq!(Ok(16), producer.try_push(b"AAAAB"));
+        assert_eq!(Ok(8), producer.clone().try_push(b""));
+        assert_eq!(24, consumer.get_reserve());
+
+        assert_eq!(Ok(5), consumer.try_pop(&mut dst));
+        assert_eq!(b"AAAAB", &dst[..5]);
+        assert_eq!(Ok(0), consumer.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Empty), consumer.try_pop(&mut dst));
+        assert!(consumer.is_empty());
+        // consumed records are zeroed for the next lap
+        assert!(buffer[MPSC_CONTROL_SIZE/8..].iter().all(|&word| word == 0));
+    }
+
+    /// Verifies the whole buffer can be used and what happens when it cannot hold a message
+    #[test]
+    fn full_and_too_large(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let producer = unsafe{ MpscProducer::new(TEST_SHM_SIZE, ptr) };
+        let mut consumer = unsafe{ MpscConsumer::new(TEST_SHM_SIZE, ptr) };
+
+        assert_eq!(Err(RingError::MessageTooLarge { len: 57, max: 56 }), producer.try_push(&[1;57]));
+        assert_eq!(Ok(64), producer.try_push(&[1;56]));
+        assert_eq!(Err(RingError::Full), producer.try_push(b""));
+        assert_eq!(0, producer.push(b""));
+
+        let mut dst = [0;8];
+        assert_eq!(Err(RingError::DestinationTooSmall { needed: 56, available: 8 }), consumer.try_pop(&mut dst));
+        let mut dst = [0;56];
+        assert_eq!(Ok(56), consumer.try_pop(&mut dst));
+        assert_eq!([1;56], dst);
+    }
+
+    /// Verifies a record that does not fit before the end skips the rest of the buffer
+    #[test]
+    fn wrap_with_padding(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let producer = unsafe{ MpscProducer::new(TEST_SHM_SIZE, ptr) };
+        let mut consumer = unsafe{ MpscConsumer::new(TEST_SHM_SIZE, ptr) };
+        let mut dst = [0;64];
+
+        assert_eq!(Ok(40), producer.try_push(&[1;32]));
+        assert_eq!(Ok(32), consumer.try_pop(&mut dst));
+        // only 24 bytes are left before the end, so they become padding
+        assert_eq!(Ok(32), producer.try_push(&[2;24]));
+        assert_eq!(96, consumer.get_reserve());
+        assert_eq!(Ok(24), consumer.try_pop(&mut dst));
+        assert_eq!([2;24], dst[..24]);
+        assert_eq!(96, consumer.get_head());
+
+        // with the head in the middle, only the padding fits, it is published on its own
+        assert_eq!(Ok(16), producer.try_push(&[3;8]));
+        assert_eq!(Ok(8), consumer.try_pop(&mut dst));
+        assert_eq!(Err(RingError::Full), producer.try_push(&[4;48]));
+        assert_eq!(Err(RingError::Empty), consumer.try_pop(&mut dst));
+        assert_eq!(128, consumer.get_head());
+        assert_eq!(Ok(56), producer.try_push(&[4;48]));
+        assert_eq!(Ok(48), consumer.try_pop(&mut dst));
+        assert_eq!([4;48], dst[..48]);
+    }
+
+    /// Verifies every producer's messages arrive whole and in the order it pushed them
+    #[test]
+    fn producers_across_threads(){
+        const PRODUCERS: u32 = 4;
+        const MSGS: u32 = 2_000;
+        let size = MPSC_CONTROL_SIZE + 256;
+        let mut buffer: Vec<u64> = vec![0;size/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let producer = unsafe{ MpscProducer::new(size, ptr) };
+        let mut consumer = unsafe{ MpscConsumer::new(size, ptr) };
+
+        std::thread::scope(|s| {
+            for id in 0..PRODUCERS {
+                let producer = producer.clone();
+                s.spawn(move || {
+                    for i in 0..MSGS {
+                        // vary the length so records land on every offset
+                        let msg = [id.to_le_bytes(), i.to_le_bytes(), i.to_le_bytes()].concat();
+                        let len = 8 + i as usize % 5;
+                        while producer.push(&msg[..len]) == 0 {
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+            }
+            s.spawn(move || {
+                let mut next = [0u32; PRODUCERS as usize];
+                let mut dst = [0;12];
+                for _ in 0..PRODUCERS * MSGS {
+                    let mut len = consumer.pop(&mut dst);
+                    while len == 0 {
+                        std::thread::yield_now();
+                        len = consumer.pop(&mut dst);
+                    }
+                    let id = u32::from_le_bytes(dst[..4].try_into().unwrap()) as usize;
+                    let i = u32::from_le_bytes(dst[4..8].try_into().unwrap());
+                    assert_eq!(next[id], i);
+                    assert_eq!(8 + i as usize % 5, len);
+                    next[id] += 1;
+                }
+                assert!(consumer.is_empty());
+            });
+        });
+    }
+}
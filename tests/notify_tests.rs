@@ -0,0 +1,85 @@
+#[cfg(all(test, target_os = "linux"))]
+mod notify_tests{
+    use shm_ring::{
+            CONTROL_SIZE,
+            notify::Notifier,
+            ringbuffer_ro::RingbufRo,
+            ringbuffer_rw::RingbufRw,
+    };
+    use std::os::{fd::AsRawFd, unix::net::UnixStream};
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 36;//tail, head and wait words, 36 for buffer
+
+    /// Verifies the eventfd survives the trip over a unix socket
+    #[test]
+    fn send_over_socket(){
+        let (left, right) = UnixStream::pair().unwrap();
+        let notifier = Notifier::new().unwrap();
+        notifier.send(&left).unwrap();
+        let received = Notifier::recv(&right).unwrap();
+        assert_ne!(notifier.as_raw_fd(), received.as_raw_fd());
+
+        assert_eq!(0, received.drain().unwrap());
+        notifier.notify().unwrap();
+        notifier.notify().unwrap();
+        assert_eq!(2, received.drain().unwrap());
+        assert_eq!(0, received.drain().unwrap());
+    }
+
+    /// Verifies push only signals after the consumer announced it is idle, and only once
+    #[test]
+    fn push_signals_idle_consumer(){
+        let mut buffer: Vec<u8> = vec![0;TEST_SHM_SIZE];
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, buffer.as_mut_ptr()) };
+        let notifier = Notifier::new().unwrap();
+        w_ring.set_notifier(Some(notifier.try_clone().unwrap()));
+
+        w_ring.push(b"AAAA");
+        assert_eq!(0, notifier.drain().unwrap());
+        // there is still a message, so the consumer should not go to sleep
+        assert!(!r_ring.announce_idle());
+
+        let mut dst = [0;4];
+        assert_eq!(4, r_ring.pop(&mut dst));
+        assert!(r_ring.announce_idle());
+        w_ring.push(b"BBBB");
+        w_ring.push(b"CCCC");
+        assert_eq!(1, notifier.drain().unwrap());
+    }
+
+    /// Verifies a consumer that sleeps in poll is woken by a producer on another thread
+    #[test]
+    fn poll_loop_across_threads(){
+        const MSGS: u32 = 2_000;
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE.div_ceil(8)];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut r_ring = unsafe{ RingbufRo::new(TEST_SHM_SIZE, ptr) };
+        let mut w_ring = unsafe{ RingbufRw::new(TEST_SHM_SIZE, ptr) };
+        let (left, right) = UnixStream::pair().unwrap();
+        let notifier = Notifier::new().unwrap();
+        notifier.send(&left).unwrap();
+        w_ring.set_notifier(Some(Notifier::recv(&right).unwrap()));
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..MSGS {
+                    w_ring.push_blocking(&i.to_le_bytes(), None).unwrap();
+                }
+            });
+            s.spawn(move || {
+                let mut dst = [0;4];
+                let mut i = 0;
+                while i < MSGS {
+                    if r_ring.pop(&mut dst) == 4 {
+                        assert_eq!(i.to_le_bytes(), dst);
+                        i += 1;
+                    } else if r_ring.announce_idle() {
+                        let mut fd = libc::pollfd { fd: notifier.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+                        assert_eq!(1, unsafe { libc::poll(&mut fd, 1, 5_000) });
+                        notifier.drain().unwrap();
+                    }
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,290 @@
+#[cfg(test)]
+mod shm_tests{
+    use shm_ring::{
+            CONTROL_SIZE,
+            LenWidth,
+            error::ShmError,
+            header::{FLAG_CONTIGUOUS, FLAG_HUGE_PAGES, FLAG_LOSSY, FLAG_MIRRORED, HEADER_SIZE, MAGIC, VERSION, SegmentHeader},
+            shm::ShmRing,
+    };
+    use std::os::fd::{AsRawFd, BorrowedFd};
+
+    fn test_name(test: &str) -> String {
+        format!("/shm_ring_{}_{}", test, std::process::id())
+    }
+
+    /// Verifies a message pushed through one mapping can be popped through another
+    #[test]
+    fn create_and_open(){
+        let name = test_name("create_and_open");
+        let mut owner = ShmRing::create(&name, 64).unwrap();
+        let mut peer = ShmRing::open(&name).unwrap();
+        assert_eq!(64, owner.capacity());
+        assert_eq!(64, peer.capacity());
+
+        let msg = b"AAAABBBB";
+        let mut writer = owner.producer().unwrap();
+        assert_eq!(msg.len() + LenWidth::default().bytes(), writer.push(msg));
+
+        let mut reader = peer.consumer().unwrap();
+        let mut buffer = [0;8];
+        assert_eq!(msg.len(), reader.pop(&mut buffer));
+        assert_eq!(msg, &buffer);
+        assert!(writer.is_empty());
+    }
+
+    /// Verifies the creator removes the name when it is dropped
+    #[test]
+    fn drop_unlinks(){
+        let name = test_name("drop_unlinks");
+        let owner = ShmRing::create(&name, 64).unwrap();
+        assert!(ShmRing::create(&name, 64).is_err());
+        drop(owner);
+        assert!(ShmRing::open(&name).is_err());
+    }
+
+    /// Verifies a segment that is too small for a ring is rejected
+    #[test]
+    fn capacity_too_small(){
+        let name = test_name("capacity_too_small");
+        assert!(matches!(ShmRing::create(&name, 1), Err(ShmError::Io(_))));
+        assert!(matches!(ShmRing::open(&name), Err(ShmError::Io(_))));
+    }
+
+    /// Verifies a memfd segment can be attached through a duplicate of its fd
+    #[test]
+    fn anonymous_from_fd(){
+        let mut owner = ShmRing::create_anonymous(64).unwrap();
+        let fd = unsafe { BorrowedFd::borrow_raw(owner.as_raw_fd()) }.try_clone_to_owned().unwrap();
+        let mut peer = ShmRing::from_fd(fd).unwrap();
+
+        let msg = b"AAAABBBB";
+        owner.producer().unwrap().push(msg);
+        let mut buffer = [0;8];
+        assert_eq!(msg.len(), peer.consumer().unwrap().pop(&mut buffer));
+        assert_eq!(msg, &buffer);
+    }
+
+    /// Verifies split hands out both halves of one mapping
+    #[test]
+    fn split(){
+        let mut ring = ShmRing::create_anonymous(64).unwrap();
+        let (mut writer, mut reader) = unsafe { ring.split() }.unwrap();
+        let msg = b"AAAA";
+        writer.push(msg);
+        let mut buffer = [0;4];
+        assert_eq!(msg.len(), reader.pop(&mut buffer));
+        assert_eq!(msg, &buffer);
+    }
+
+    /// Verifies the header written on create is what a peer reads back
+    #[test]
+    fn header_round_trip(){
+        let ring = ShmRing::create_anonymous(64).unwrap();
+        let header = ring.header();
+        assert_eq!(VERSION, header.version());
+        assert_eq!(LenWidth::default().bytes() as u32, header.len_width());
+        assert_eq!(64, header.capacity());
+        assert_eq!(0, header.flags());
+    }
+
+    /// Verifies attaching to a file that was never laid out as a ring is refused
+    #[test]
+    fn attach_bad_magic(){
+        let path = std::env::temp_dir().join(format!("shm_ring_bad_magic_{}", std::process::id()));
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        file.set_len(4096).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(ShmRing::from_fd(file.into()), Err(ShmError::BadMagic { found: 0 })));
+    }
+
+    /// Verifies every field of the header is checked on attach
+    #[test]
+    fn attach_mismatched_header(){
+        let size = HEADER_SIZE + CONTROL_SIZE + 64;
+        let mut buffer: Vec<u64> = vec![0;size/8];
+        let data = buffer.as_mut_ptr() as *mut u8;
+
+        unsafe { SegmentHeader::init(data, 64, 0) };
+        assert!(unsafe { SegmentHeader::attach(data, size) }.is_ok());
+        assert_eq!(MAGIC, buffer[0]);
+        assert_eq!(shm_ring::CACHE_LINE, HEADER_SIZE);
+
+        // the segment is smaller than the header claims
+        assert!(matches!(unsafe { SegmentHeader::attach(data, size - 1) }, Err(ShmError::CapacityMismatch { header: 64, .. })));
+
+        // the buffer cannot start inside the header or the control block
+        buffer[5] = HEADER_SIZE as u64;
+        assert!(matches!(unsafe { SegmentHeader::attach(data, size) }, Err(ShmError::CapacityMismatch { header: 64, .. })));
+        buffer[5] = (HEADER_SIZE + CONTROL_SIZE) as u64;
+
+        // version and length width share the second word
+        buffer[1] = (VERSION + 1) as u64 | ((LenWidth::default().bytes() as u64) << 32);
+        assert!(matches!(unsafe { SegmentHeader::attach(data, size) }, Err(ShmError::VersionMismatch { .. })));
+        buffer[1] = VERSION as u64 | (3 << 32);
+        assert!(matches!(unsafe { SegmentHeader::attach(data, size) }, Err(ShmError::LenWidthMismatch { found: 3, .. })));
+
+        unsafe { SegmentHeader::init(data, 64, 1 << 63) };
+        assert!(matches!(unsafe { SegmentHeader::attach(data, size) }, Err(ShmError::UnsupportedFlags { flags: 0x8000_0000_0000_0000 })));
+    }
+
+    /// Verifies the halves pick up the contiguous flag from the header
+    #[test]
+    fn contiguous_flag(){
+        let name = test_name("contiguous_flag");
+        let _owner = ShmRing::create_with_flags(&name, 64, FLAG_CONTIGUOUS).unwrap();
+        let mut peer = ShmRing::open(&name).unwrap();
+        assert_eq!(FLAG_CONTIGUOUS, peer.header().flags());
+        let (writer, reader) = unsafe { peer.split() }.unwrap();
+        assert!(writer.is_contiguous());
+        assert!(reader.is_contiguous());
+
+        assert!(matches!(ShmRing::create_anonymous_with_flags(64, 1 << 40), Err(ShmError::UnsupportedFlags { .. })));
+    }
+
+    /// Verifies both halves are set up for a lossy ring
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn lossy_flag(){
+        let mut ring = ShmRing::create_anonymous_with_flags(64, FLAG_LOSSY).unwrap();
+        let (mut writer, reader) = unsafe { ring.split() }.unwrap();
+        assert!(writer.is_lossy());
+        assert!(reader.is_lossy());
+        assert!(!reader.is_contiguous());
+
+        for _ in 0..10 {
+            assert_eq!(16, writer.push(b"AAAABBBB"));
+        }
+        assert!(writer.get_dropped() > 0);
+    }
+
+    /// Verifies a slot ring segment records its slot size and checks it on attach
+    #[test]
+    fn slot_ring(){
+        let name = test_name("slot_ring");
+        let mut owner = ShmRing::create_slots::<u32>(&name, 16).unwrap();
+        let mut peer = ShmRing::open(&name).unwrap();
+        assert_eq!(4, peer.header().slot_size());
+        assert_eq!(64, peer.capacity());
+        assert!(matches!(peer.slot_ring::<u64>(), Err(ShmError::SlotSizeMismatch { expected: 8, found: 4 })));
+        assert!(matches!(peer.consumer(), Err(ShmError::SlotSizeMismatch { expected: 0, found: 4 })));
+
+        let mut producer = owner.slot_ring::<u32>().unwrap();
+        assert_eq!(16, producer.get_size());
+        assert_eq!(5, producer.push_slice(&[1, 2, 3, 4, 5]));
+        let mut consumer = peer.slot_ring::<u32>().unwrap();
+        assert_eq!(Some(1), consumer.pop());
+        let mut out = [0;8];
+        assert_eq!(4, consumer.pop_slice(&mut out));
+        assert_eq!(&[2, 3, 4, 5], &out[..4]);
+
+        // byte rings have no slots
+        let mut bytes = ShmRing::create_anonymous(64).unwrap();
+        assert!(matches!(bytes.slot_ring::<u8>(), Err(ShmError::SlotSizeMismatch { expected: 1, found: 0 })));
+        assert!(ShmRing::create_anonymous_slots::<u64>(1).is_err());
+    }
+
+    /// Verifies the halves pick up the length width from the header
+    #[test]
+    fn len_width(){
+        let name = test_name("len_width");
+        let mut owner = ShmRing::create_with_len_width(&name, 64, 0, LenWidth::U32).unwrap();
+        let mut peer = ShmRing::open(&name).unwrap();
+        assert_eq!(4, peer.header().len_width());
+        assert_eq!(LenWidth::U32, peer.len_width());
+
+        let mut writer = owner.producer().unwrap();
+        assert_eq!(LenWidth::U32, writer.get_len_width());
+        assert_eq!(8 + 4, writer.push(b"AAAABBBB"));
+
+        let mut reader = peer.consumer().unwrap();
+        let mut buffer = [0;8];
+        assert_eq!(8, reader.pop(&mut buffer));
+        assert_eq!(b"AAAABBBB", &buffer);
+    }
+
+    /// Verifies capacities are rounded up to a power of two and a ring of that size wraps correctly
+    #[test]
+    fn round_capacity(){
+        assert_eq!(Some(64), ShmRing::round_capacity(64));
+        assert_eq!(Some(4096), ShmRing::round_capacity(4095));
+        assert_eq!(None, ShmRing::round_capacity(usize::MAX));
+
+        let mut ring = ShmRing::create_anonymous(ShmRing::round_capacity(40).unwrap()).unwrap();
+        assert_eq!(64, ring.capacity());
+        let (mut writer, mut reader) = unsafe { ring.split() }.unwrap();
+        let mut buffer = [0;20];
+        for i in 0..10u8 {
+            assert_eq!(28, writer.push(&[i;20]));
+            assert_eq!(20, reader.pop(&mut buffer));
+            assert_eq!([i;20], buffer);
+        }
+        assert_eq!(280, reader.get_head());
+    }
+
+    /// Verifies a mirrored segment hands out messages that wrap the end of the buffer as one slice
+    #[test]
+    fn mirrored(){
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        assert!(matches!(ShmRing::create_anonymous_with_flags(page_size + 64, FLAG_MIRRORED), Err(ShmError::Io(_))));
+
+        let name = test_name("mirrored");
+        let mut owner = ShmRing::create_with_flags(&name, page_size, FLAG_MIRRORED).unwrap();
+        let mut peer = ShmRing::open(&name).unwrap();
+        assert!(peer.is_mirrored());
+        assert_eq!(page_size, peer.header().buffer_offset());
+        assert_eq!(page_size, peer.capacity());
+
+        // leave the tail 24 bytes short of the end, so the next message wraps
+        let mut writer = owner.producer().unwrap();
+        assert!(writer.is_mirrored());
+        let filler = vec![1u8; page_size - 32];
+        writer.push(&filler);
+        let mut reader = peer.consumer().unwrap();
+        assert!(reader.is_mirrored());
+        let mut buffer = vec![0u8; page_size];
+        assert_eq!(filler.len(), reader.pop(&mut buffer));
+
+        let msg: Vec<u8> = (0..100).collect();
+        writer.push(&msg);
+        let guard = reader.read().unwrap();
+        assert_eq!(Some(&msg[..]), guard.as_slice());
+        drop(guard);
+        assert!(writer.is_empty());
+
+        writer.push(&msg);
+        assert_eq!(msg.len(), reader.pop(&mut buffer));
+        assert_eq!(&msg[..], &buffer[..msg.len()]);
+    }
+
+    /// Verifies a segment that asks for huge pages works whether or not it got them, and reports which it got
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn huge_pages(){
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let plain = ShmRing::create_anonymous(64).unwrap();
+        assert_eq!(page_size, plain.page_size());
+
+        let mut owner = ShmRing::create_anonymous_with_flags(64, FLAG_HUGE_PAGES).unwrap();
+        assert!(owner.page_size() >= page_size);
+        assert!(owner.page_size().is_power_of_two());
+        let fd = unsafe { BorrowedFd::borrow_raw(owner.as_raw_fd()) }.try_clone_to_owned().unwrap();
+        let mut peer = ShmRing::from_fd(fd).unwrap();
+        assert_eq!(owner.page_size(), peer.page_size());
+        assert_eq!(FLAG_HUGE_PAGES, peer.header().flags());
+
+        let msg = b"AAAABBBB";
+        owner.producer().unwrap().push(msg);
+        let mut buffer = [0;8];
+        assert_eq!(msg.len(), peer.consumer().unwrap().pop(&mut buffer));
+        assert_eq!(msg, &buffer);
+
+        // named segments and mirrored ones fall back to transparent huge pages
+        let name = test_name("huge_pages");
+        let named = ShmRing::create_with_flags(&name, 64, FLAG_HUGE_PAGES).unwrap();
+        assert_eq!(page_size, named.page_size());
+        let mirrored = ShmRing::create_anonymous_with_flags(page_size, FLAG_HUGE_PAGES | FLAG_MIRRORED).unwrap();
+        assert_eq!(page_size, mirrored.page_size());
+        assert!(mirrored.is_mirrored());
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod slot_ring_tests{
+    use shm_ring::{
+            CONTROL_SIZE,
+            error::RingError,
+            slot_ring::SlotRing,
+    };
+    const TEST_SHM_SIZE: usize = CONTROL_SIZE + 4 * 8;
+
+    /// Verifies values come out in order and every slot can be filled
+    #[test]
+    fn push_and_pop(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut producer = unsafe{ SlotRing::<u64>::new(TEST_SHM_SIZE, ptr) };
+        let mut consumer = unsafe{ SlotRing::<u64>::new(TEST_SHM_SIZE, ptr) };
+        assert_eq!(4, consumer.get_size());
+        assert!(consumer.is_empty());
+        assert_eq!(None, consumer.pop());
+
+        assert_eq!(Ok(()), producer.push(1));
+        assert_eq!(Ok(()), producer.push(2));
+        assert_eq!(Ok(()), producer.push(3));
+        assert_eq!(Ok(()), producer.push(4));
+        assert!(producer.is_full());
+        assert_eq!(Err(RingError::Full), producer.push(5));
+        // no length field, the values sit right after the control block
+        assert_eq!(&[1, 2, 3, 4], &buffer[CONTROL_SIZE/8..CONTROL_SIZE/8 + 4]);
+
+        assert_eq!(Some(1), consumer.pop());
+        assert_eq!(Ok(()), producer.push(5));
+        assert_eq!(5, producer.get_tail());
+        assert_eq!(Err(RingError::Full), producer.push(6));
+        assert_eq!(Some(2), consumer.pop());
+        assert_eq!(Ok(()), producer.push(6));
+        for value in 3..=6 {
+            assert_eq!(Some(value), consumer.pop());
+        }
+        assert_eq!(None, consumer.pop());
+    }
+
+    /// Verifies the batch calls stop at the free or full slots and wrap the end of the buffer
+    #[test]
+    fn batches(){
+        let mut buffer: Vec<u64> = vec![0;TEST_SHM_SIZE/8];
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut producer = unsafe{ SlotRing::<[u16;4]>::new(TEST_SHM_SIZE, ptr) };
+        let mut consumer = unsafe{ SlotRing::<[u16;4]>::new(TEST_SHM_SIZE, ptr) };
+
+        let values: Vec<[u16;4]> = (0..5).map(|i| [i;4]).collect();
+        let mut out = [[0u16;4];4];
+        assert_eq!(0, consumer.pop_slice(&mut out));
+        assert_eq!(4, producer.push_slice(&values));
+        assert_eq!(0, producer.push_slice(&values[4..]));
+        assert_eq!(2, consumer.pop_slice(&mut out[..2]));
+        assert_eq!(&values[..2], &out[..2]);
+
+        // wraps around the end of the buffer
+        assert_eq!(1, producer.push_slice(&values[4..]));
+        assert_eq!(5, producer.get_tail());
+        assert_eq!(3, consumer.pop_slice(&mut out));
+        assert_eq!(&values[2..], &out[..3]);
+        assert!(consumer.is_empty());
+    }
+
+    /// Verifies every value makes it across threads in order
+    #[test]
+    fn across_threads(){
+        const COUNT: u64 = 10_000;
+        let mut buffer: Vec<u64> = vec![0;(CONTROL_SIZE + 64 * 8)/8];
+        let size = buffer.len() * 8;
+        let ptr = buffer.as_mut_ptr() as *mut u8;
+        let mut producer = unsafe{ SlotRing::<u64>::new(size, ptr) };
+        let mut consumer = unsafe{ SlotRing::<u64>::new(size, ptr) };
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                let values: Vec<u64> = (0..COUNT).collect();
+                let mut sent = 0;
+                while sent < values.len() {
+                    let pushed = producer.push_slice(&values[sent..(sent + 7).min(values.len())]);
+                    if pushed == 0 {std::thread::yield_now();}
+                    sent += pushed;
+                }
+            });
+
+            let mut expected = 0;
+            while expected < COUNT {
+                match consumer.pop() {
+                    Some(value) => {
+                        assert_eq!(expected, value);
+                        expected += 1;
+                    }
+                    None => std::thread::yield_now(),
+                }
+            }
+        });
+    }
+}